@@ -1 +1,6 @@
+pub mod check;
 pub mod command;
+pub mod component;
+pub mod ratelimit;
+pub mod register;
+pub mod shutdown;
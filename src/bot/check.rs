@@ -0,0 +1,55 @@
+use crate::bot::ratelimit;
+use crate::i18n::Locale;
+use crate::{Context, Error};
+
+/// スタッフだけが使えるコマンドの印。`custom_data = "StaffOnly"`で付け、ヘルプで使う
+pub struct StaffOnly;
+
+pub async fn is_staff(ctx: Context<'_>) -> Result<bool, Error> {
+    let Some(member) = ctx.author_member().await else {
+        return Ok(false);
+    };
+    Ok(member
+        .roles
+        .contains(&ctx.data().config.guild.staff_role_id))
+}
+
+/// 設定されたクールダウンを適用する。スタッフは対象外
+pub async fn cooldown(ctx: Context<'_>) -> Result<bool, Error> {
+    // 補完は実行ではなく、メッセージも返せないので数えない
+    if let poise::Context::Application(actx) = ctx
+        && actx.interaction_type == poise::CommandInteractionType::Autocomplete
+    {
+        return Ok(true);
+    }
+
+    let name = &ctx.command().qualified_name;
+    let Some(cooldown) = ctx.data().config.cooldowns.get(name) else {
+        return Ok(true);
+    };
+
+    if is_staff(ctx).await? {
+        return Ok(true);
+    }
+
+    let key = ratelimit::scope_key(
+        cooldown.scope,
+        ctx.author().id,
+        ctx.channel_id(),
+        ctx.guild_id(),
+    );
+
+    match ratelimit::hit(name, key, ctx.id(), cooldown) {
+        Some(wait) => {
+            ctx.send(
+                poise::CreateReply::default()
+                    .content(ratelimit::message(wait, Locale::of(ctx)))
+                    .ephemeral(true),
+            )
+            .await?;
+
+            Ok(false)
+        }
+        None => Ok(true),
+    }
+}
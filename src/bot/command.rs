@@ -1,3 +1,6 @@
+use crate::bot::check::{StaffOnly, is_staff};
+use crate::bot::register;
+use crate::i18n::{Locale, t};
 use crate::{Context, Error};
 
 /// pong
@@ -6,3 +9,64 @@ pub async fn ping(ctx: Context<'_>) -> Result<(), Error> {
     ctx.say("pong 🦀").await?;
     Ok(())
 }
+
+/// ボットの管理（スタッフのみ）
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("admin_sync"),
+    subcommand_required,
+    custom_data = "StaffOnly"
+)]
+pub async fn admin(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// スラッシュコマンドを登録し直します
+#[poise::command(slash_command, guild_only, rename = "sync")]
+pub async fn admin_sync(
+    ctx: Context<'_>,
+    #[description = "変更がないコマンドも登録し直す"] force: Option<bool>,
+) -> Result<(), Error> {
+    let locale = Locale::of(ctx);
+
+    if !is_staff(ctx).await? {
+        ctx.send(
+            poise::CreateReply::default()
+                .content(t!(locale, "common.no_permission"))
+                .ephemeral(true),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    ctx.defer_ephemeral().await?;
+
+    let reports = register::sync_all(
+        ctx.serenity_context(),
+        &ctx.data().config,
+        &ctx.framework().options().commands,
+        force.unwrap_or(false),
+    )
+    .await?;
+    let lines = reports
+        .iter()
+        .map(|(scope, report)| {
+            t!(
+                locale,
+                "admin.sync_line",
+                scope = scope,
+                upserted = report.upserted.len(),
+                deleted = report.deleted.len(),
+                unchanged = report.unchanged
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ctx.say(format!("{}\n{lines}", t!(locale, "admin.synced")))
+        .await?;
+
+    Ok(())
+}
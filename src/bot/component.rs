@@ -0,0 +1,344 @@
+use crate::i18n::{Locale, t};
+use crate::{Data, Error};
+use futures::future::BoxFuture;
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// Discordのcustom_idの最大文字数
+pub const MAX_CUSTOM_ID_LEN: usize = 100;
+
+const SEPARATOR: char = ':';
+
+/// custom_idに埋め込む型付きのデータ
+///
+/// `{NAMESPACE}:{fields...}`の形式で、`VERSION`が1以上なら`{NAMESPACE}:v{VERSION}:{fields...}`になる
+pub trait ComponentId: Sized {
+    const NAMESPACE: &'static str;
+    /// 形式を変えたら上げる。違うバージョンのcustom_idは古いものとして扱う
+    const VERSION: u32 = 0;
+
+    fn fields(&self) -> Vec<String>;
+
+    fn parse(fields: &[&str]) -> Option<Self>;
+
+    fn encode(&self) -> Result<String, Error> {
+        let fields = self.fields();
+
+        if let Some(field) = fields.iter().find(|f| f.contains(SEPARATOR)) {
+            return Err(format!("custom_id field contains '{SEPARATOR}': {field}").into());
+        }
+
+        let mut parts = vec![Self::NAMESPACE.to_owned()];
+
+        if Self::VERSION > 0 {
+            parts.push(format!("v{}", Self::VERSION));
+        }
+
+        parts.extend(fields);
+
+        let custom_id = parts.join(":");
+
+        if custom_id.chars().count() > MAX_CUSTOM_ID_LEN {
+            return Err(
+                format!("custom_id is longer than {MAX_CUSTOM_ID_LEN}: {custom_id}").into(),
+            );
+        }
+
+        Ok(custom_id)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    Namespace,
+    /// 違うバージョンで作られたもの
+    Version(u32),
+    Fields,
+}
+
+pub fn decode<T: ComponentId>(custom_id: &str) -> Result<T, DecodeError> {
+    let parts: Vec<&str> = custom_id.split(SEPARATOR).collect();
+    let Some((namespace, rest)) = parts.split_first() else {
+        return Err(DecodeError::Namespace);
+    };
+
+    if *namespace != T::NAMESPACE {
+        return Err(DecodeError::Namespace);
+    }
+
+    let version = rest
+        .first()
+        .and_then(|p| p.strip_prefix('v'))
+        .and_then(|v| v.parse::<u32>().ok());
+    let (version, fields) = match version {
+        Some(version) => (version, &rest[1..]),
+        None => (0, rest),
+    };
+
+    if version != T::VERSION {
+        return Err(DecodeError::Version(version));
+    }
+
+    T::parse(fields).ok_or(DecodeError::Fields)
+}
+
+/// custom_idを持つインタラクション（ボタン・セレクトメニュー・モーダル）
+pub trait RoutedInteraction: Send + Sync {
+    /// ログに出す種類
+    const KIND: &'static str;
+
+    fn custom_id(&self) -> &str;
+
+    fn locale(&self) -> Locale;
+
+    fn respond<'a>(
+        &'a self,
+        ctx: &'a serenity::Context,
+        response: serenity::CreateInteractionResponse,
+    ) -> BoxFuture<'a, Result<(), serenity::Error>>;
+}
+
+impl RoutedInteraction for serenity::ComponentInteraction {
+    const KIND: &'static str = "component";
+
+    fn custom_id(&self) -> &str {
+        &self.data.custom_id
+    }
+
+    fn locale(&self) -> Locale {
+        Locale::from_discord(&self.locale)
+    }
+
+    fn respond<'a>(
+        &'a self,
+        ctx: &'a serenity::Context,
+        response: serenity::CreateInteractionResponse,
+    ) -> BoxFuture<'a, Result<(), serenity::Error>> {
+        Box::pin(self.create_response(ctx, response))
+    }
+}
+
+impl RoutedInteraction for serenity::ModalInteraction {
+    const KIND: &'static str = "modal";
+
+    fn custom_id(&self) -> &str {
+        &self.data.custom_id
+    }
+
+    fn locale(&self) -> Locale {
+        Locale::from_discord(&self.locale)
+    }
+
+    fn respond<'a>(
+        &'a self,
+        ctx: &'a serenity::Context,
+        response: serenity::CreateInteractionResponse,
+    ) -> BoxFuture<'a, Result<(), serenity::Error>> {
+        Box::pin(self.create_response(ctx, response))
+    }
+}
+
+trait Route<I>: Send + Sync {
+    fn handle<'a>(
+        &'a self,
+        ctx: &'a serenity::Context,
+        data: &'a Data,
+        i: &'a I,
+    ) -> BoxFuture<'a, Result<(), Error>>;
+}
+
+struct TypedRoute<T, F> {
+    handler: F,
+    _id: PhantomData<fn() -> T>,
+}
+
+impl<I, T, F> Route<I> for TypedRoute<T, F>
+where
+    I: RoutedInteraction,
+    T: ComponentId,
+    F: for<'a> Fn(&'a serenity::Context, &'a Data, &'a I, T) -> BoxFuture<'a, Result<(), Error>>
+        + Send
+        + Sync,
+{
+    fn handle<'a>(
+        &'a self,
+        ctx: &'a serenity::Context,
+        data: &'a Data,
+        i: &'a I,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        match decode::<T>(i.custom_id()) {
+            Ok(id) => (self.handler)(ctx, data, i, id),
+            Err(err) => Box::pin(reject(ctx, i, err)),
+        }
+    }
+}
+
+async fn reject<I: RoutedInteraction>(
+    ctx: &serenity::Context,
+    i: &I,
+    err: DecodeError,
+) -> Result<(), Error> {
+    tracing::debug!("undecodable {} {}: {err:?}", I::KIND, i.custom_id());
+
+    i.respond(
+        ctx,
+        serenity::CreateInteractionResponse::Message(
+            serenity::CreateInteractionResponseMessage::new()
+                .content(t!(i.locale(), "common.outdated"))
+                .ephemeral(true),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+type Routes<I> = HashMap<&'static str, Box<dyn Route<I>>>;
+
+type AutocompleteHandler = Box<
+    dyn for<'a> Fn(crate::Context<'a>, &'a str) -> BoxFuture<'a, Vec<serenity::AutocompleteChoice>>
+        + Send
+        + Sync,
+>;
+
+async fn dispatch<I: RoutedInteraction>(
+    routes: &Routes<I>,
+    ctx: &serenity::Context,
+    data: &Data,
+    i: &I,
+) -> Result<(), Error> {
+    let namespace = i.custom_id().split(SEPARATOR).next().unwrap_or("");
+
+    match routes.get(namespace) {
+        Some(route) => route.handle(ctx, data, i).await,
+        None => {
+            tracing::warn!("unknown {}: {}", I::KIND, i.custom_id());
+
+            Ok(())
+        }
+    }
+}
+
+/// custom_idの名前空間ごとに処理を振り分ける
+#[derive(Default)]
+pub struct Router {
+    components: Routes<serenity::ComponentInteraction>,
+    modals: Routes<serenity::ModalInteraction>,
+    /// `{コマンド名}:{オプション名}`ごとの補完
+    autocompletes: HashMap<String, AutocompleteHandler>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `T::NAMESPACE`のボタンやセレクトメニューの処理を登録する
+    pub fn component<T, F>(mut self, handler: F) -> Self
+    where
+        T: ComponentId + 'static,
+        F: for<'a> Fn(
+                &'a serenity::Context,
+                &'a Data,
+                &'a serenity::ComponentInteraction,
+                T,
+            ) -> BoxFuture<'a, Result<(), Error>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.components.insert(
+            T::NAMESPACE,
+            Box::new(TypedRoute {
+                handler,
+                _id: PhantomData,
+            }),
+        );
+
+        self
+    }
+
+    /// `T::NAMESPACE`のモーダルの処理を登録する
+    pub fn modal<T, F>(mut self, handler: F) -> Self
+    where
+        T: ComponentId + 'static,
+        F: for<'a> Fn(
+                &'a serenity::Context,
+                &'a Data,
+                &'a serenity::ModalInteraction,
+                T,
+            ) -> BoxFuture<'a, Result<(), Error>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.modals.insert(
+            T::NAMESPACE,
+            Box::new(TypedRoute {
+                handler,
+                _id: PhantomData,
+            }),
+        );
+
+        self
+    }
+
+    /// コマンドのオプションの補完を登録する。オプションには`#[autocomplete = "route_autocomplete"]`を付ける
+    pub fn autocomplete<F>(mut self, command: &str, option: &str, handler: F) -> Self
+    where
+        F: for<'a> Fn(
+                crate::Context<'a>,
+                &'a str,
+            ) -> BoxFuture<'a, Vec<serenity::AutocompleteChoice>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.autocompletes
+            .insert(format!("{command}{SEPARATOR}{option}"), Box::new(handler));
+
+        self
+    }
+
+    pub async fn dispatch(
+        &self,
+        ctx: &serenity::Context,
+        data: &Data,
+        interaction: &serenity::Interaction,
+    ) -> Result<(), Error> {
+        match interaction {
+            serenity::Interaction::Component(i) => dispatch(&self.components, ctx, data, i).await,
+            serenity::Interaction::Modal(i) => dispatch(&self.modals, ctx, data, i).await,
+            // スラッシュコマンドはpoiseが振り分け、補完はpoiseから`route_autocomplete`に届く
+            _ => Ok(()),
+        }
+    }
+}
+
+/// poiseの`#[autocomplete]`から呼ばれ、コマンド名と入力中のオプション名で補完を振り分ける
+pub async fn route_autocomplete(
+    ctx: crate::Context<'_>,
+    partial: &str,
+) -> Vec<serenity::AutocompleteChoice> {
+    let poise::Context::Application(app) = ctx else {
+        return Vec::new();
+    };
+    let Some(focused) = app.interaction.data.autocomplete() else {
+        return Vec::new();
+    };
+    let key = format!(
+        "{}{SEPARATOR}{}",
+        ctx.command().qualified_name,
+        focused.name
+    );
+
+    match ctx.data().router.autocompletes.get(&key) {
+        Some(handler) => handler(ctx, partial).await,
+        None => {
+            tracing::warn!("unknown autocomplete: {key}");
+
+            Vec::new()
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::config::{Cooldown, CooldownScope};
+use crate::i18n::{Locale, t};
+use dashmap::DashMap;
+use poise::serenity_prelude as serenity;
+use std::collections::VecDeque;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// 期限の切れた記録を消す間隔
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Hits {
+    /// クールダウンの期間
+    per: Duration,
+    /// 直近の実行時刻
+    times: VecDeque<Instant>,
+    /// 最後に記録した実行のID
+    last_invocation: Option<u64>,
+}
+
+/// `(名前, スコープのID)`ごとの実行の記録
+static HITS: LazyLock<DashMap<(String, u64), Hits>> = LazyLock::new(DashMap::new);
+
+static LAST_SWEEP: LazyLock<Mutex<Instant>> = LazyLock::new(|| Mutex::new(Instant::now()));
+
+/// 最後の実行から期間が過ぎたものを消す。利用者やチャンネルごとの記録がたまり続けないように
+fn sweep(now: Instant) {
+    {
+        let mut last = LAST_SWEEP.lock().expect("failed to lock");
+
+        if now.duration_since(*last) < SWEEP_INTERVAL {
+            return;
+        }
+
+        *last = now;
+    }
+
+    HITS.retain(|_, hits| {
+        hits.times
+            .back()
+            .is_some_and(|t| now.duration_since(*t) < hits.per)
+    });
+}
+
+pub fn scope_key(
+    scope: CooldownScope,
+    user_id: serenity::UserId,
+    channel_id: serenity::ChannelId,
+    guild_id: Option<serenity::GuildId>,
+) -> u64 {
+    match scope {
+        CooldownScope::User => user_id.get(),
+        CooldownScope::Channel => channel_id.get(),
+        CooldownScope::Guild => guild_id.map_or(channel_id.get(), |g| g.get()),
+    }
+}
+
+/// 実行を記録する。上限に達していたら記録せずに待ち時間を返す
+///
+/// poiseは親コマンドの分もチェックを呼ぶので、同じ`invocation`（インタラクションのID）は一度だけ数える
+pub fn hit(name: &str, key: u64, invocation: u64, cooldown: &Cooldown) -> Option<Duration> {
+    let now = Instant::now();
+
+    sweep(now);
+
+    let mut entry = HITS.entry((name.to_owned(), key)).or_insert_with(|| Hits {
+        per: cooldown.per,
+        times: VecDeque::new(),
+        last_invocation: None,
+    });
+
+    if entry.last_invocation == Some(invocation) {
+        return None;
+    }
+
+    entry.per = cooldown.per;
+
+    let hits = &mut entry.times;
+
+    while hits
+        .front()
+        .is_some_and(|t| now.duration_since(*t) >= cooldown.per)
+    {
+        hits.pop_front();
+    }
+
+    if hits.len() >= cooldown.limit.max(1) {
+        return hits
+            .front()
+            .map(|t| cooldown.per.saturating_sub(now.duration_since(*t)));
+    }
+
+    hits.push_back(now);
+    entry.last_invocation = Some(invocation);
+
+    None
+}
+
+pub fn message(wait: Duration, locale: Locale) -> String {
+    t!(locale, "common.cooldown", secs = wait.as_secs().max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subcommand_invocation_counts_once() {
+        let cooldown = Cooldown {
+            scope: CooldownScope::Channel,
+            limit: 1,
+            per: Duration::from_secs(30),
+        };
+
+        // `/dareda start`では親と子の2回チェックが呼ばれる
+        assert_eq!(hit("dareda start", 1, 100, &cooldown), None);
+        assert_eq!(hit("dareda start", 1, 100, &cooldown), None);
+        // 次の実行は上限に達している
+        assert!(hit("dareda start", 1, 101, &cooldown).is_some());
+    }
+}
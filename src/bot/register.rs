@@ -0,0 +1,188 @@
+use crate::config::{Config, RegistrationMode};
+use crate::{Data, Error};
+use poise::serenity_prelude as serenity;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Scope {
+    Global,
+    Guild(serenity::GuildId),
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Global => f.write_str("global"),
+            Scope::Guild(guild_id) => write!(f, "guild {guild_id}"),
+        }
+    }
+}
+
+impl Scope {
+    async fn commands(self, ctx: &serenity::Context) -> Result<Vec<serenity::Command>, Error> {
+        Ok(match self {
+            Scope::Global => serenity::Command::get_global_commands_with_localizations(ctx).await?,
+            Scope::Guild(guild_id) => guild_id.get_commands_with_localizations(ctx).await?,
+        })
+    }
+
+    /// 同じ名前のコマンドがあれば上書きされる
+    async fn upsert(
+        self,
+        ctx: &serenity::Context,
+        command: serenity::CreateCommand,
+    ) -> Result<(), Error> {
+        match self {
+            Scope::Global => {
+                serenity::Command::create_global_command(ctx, command).await?;
+            }
+            Scope::Guild(guild_id) => {
+                guild_id.create_command(ctx, command).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete(self, ctx: &serenity::Context, id: serenity::CommandId) -> Result<(), Error> {
+        match self {
+            Scope::Global => serenity::Command::delete_global_command(ctx, id).await?,
+            Scope::Guild(guild_id) => guild_id.delete_command(ctx, id).await?,
+        }
+
+        Ok(())
+    }
+}
+
+/// 1つの登録先で行った変更
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub upserted: Vec<String>,
+    pub deleted: Vec<String>,
+    pub unchanged: usize,
+}
+
+impl fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} upserted, {} deleted, {} unchanged",
+            self.upserted.len(),
+            self.deleted.len(),
+            self.unchanged
+        )
+    }
+}
+
+/// Discordが省略する値（`null`・`false`・空）
+fn is_blank(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Bool(b) => !b,
+        Value::String(s) => s.is_empty(),
+        Value::Array(a) => a.is_empty(),
+        Value::Object(o) => o.is_empty(),
+        Value::Number(_) => false,
+    }
+}
+
+/// 権限などは文字列と数値のどちらでも返ってくる
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// 登録したい内容のうち、設定している値だけが登録済みのものと同じか
+fn matches(desired: &Value, existing: &Value) -> bool {
+    if is_blank(desired) && is_blank(existing) {
+        return true;
+    }
+
+    match (desired, existing) {
+        (Value::Object(d), Value::Object(e)) => d
+            .iter()
+            .all(|(key, value)| matches(value, e.get(key).unwrap_or(&Value::Null))),
+        (Value::Array(d), Value::Array(e)) => {
+            d.len() == e.len() && d.iter().zip(e).all(|(d, e)| matches(d, e))
+        }
+        _ => scalar(desired).is_some() && scalar(desired) == scalar(existing),
+    }
+}
+
+/// `commands`と登録済みのコマンドを比べて、変わったものだけを登録し直す。`force`ならすべて登録し直す
+pub async fn sync(
+    ctx: &serenity::Context,
+    scope: Scope,
+    commands: &[poise::Command<Data, Error>],
+    force: bool,
+) -> Result<SyncReport, Error> {
+    let mut existing: HashMap<String, serenity::Command> = scope
+        .commands(ctx)
+        .await?
+        .into_iter()
+        .map(|c| (c.name.clone(), c))
+        .collect();
+    let mut report = SyncReport::default();
+
+    for command in poise::builtins::create_application_commands(commands) {
+        let desired = serde_json::to_value(&command)?;
+        let name = desired["name"].as_str().unwrap_or_default().to_owned();
+        let unchanged = existing
+            .remove(&name)
+            .map(|c| serde_json::to_value(&c))
+            .transpose()?
+            .is_some_and(|e| matches(&desired, &e));
+
+        if unchanged && !force {
+            report.unchanged += 1;
+
+            continue;
+        }
+
+        scope.upsert(ctx, command).await?;
+        report.upserted.push(name);
+    }
+
+    for (name, command) in existing {
+        scope.delete(ctx, command.id).await?;
+        report.deleted.push(name);
+    }
+
+    Ok(report)
+}
+
+/// 設定された登録先すべてを同期する。使わない登録先からはコマンドを消す
+pub async fn sync_all(
+    ctx: &serenity::Context,
+    config: &Config,
+    commands: &[poise::Command<Data, Error>],
+    force: bool,
+) -> Result<Vec<(Scope, SyncReport)>, Error> {
+    let mode = config.registration.mode;
+    let guilds = if config.registration.guilds.is_empty() {
+        vec![config.guild.guild_id]
+    } else {
+        config.registration.guilds.clone()
+    };
+    let global = matches!(mode, RegistrationMode::Global | RegistrationMode::Both);
+    let per_guild = matches!(mode, RegistrationMode::Guild | RegistrationMode::Both);
+
+    let scopes = std::iter::once((Scope::Global, global))
+        .chain(guilds.into_iter().map(|g| (Scope::Guild(g), per_guild)));
+    let mut reports = Vec::new();
+
+    for (scope, enabled) in scopes {
+        let report = sync(ctx, scope, if enabled { commands } else { &[] }, force).await?;
+
+        tracing::info!("registered commands ({scope}): {report}");
+        reports.push((scope, report));
+    }
+
+    Ok(reports)
+}
@@ -0,0 +1,73 @@
+use crate::config::Config;
+use crate::logger;
+use crate::pokemon::{daily, session};
+use poise::serenity_prelude as serenity;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 進行中のクイズの結果発表を待つ時間
+const QUIZ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 状態の書き出しを待つ時間
+const PERSIST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// SIGINTかSIGTERMを受け取るまで待つ
+async fn signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!("failed to listen for ctrl-c: {err}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                tracing::error!("failed to listen for SIGTERM: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
+}
+
+/// シグナルを受け取ったらシャードを閉じる。`client.start()`はそのあとに戻る
+pub async fn watch(shard_manager: Arc<serenity::ShardManager>) {
+    signal().await;
+
+    tracing::info!("shutting down");
+
+    shard_manager.shutdown_all().await;
+}
+
+/// `client.start()`が戻ったあとの後始末。クイズを中断して発表し、状態とログを書き出す
+pub async fn finish(config: &Config) {
+    let remaining = session::stop_all(QUIZ_TIMEOUT).await;
+
+    if remaining > 0 {
+        tracing::warn!("{remaining} quiz(zes) did not finish before shutdown");
+    }
+
+    if let Some(daily) = &config.daily {
+        match tokio::time::timeout(PERSIST_TIMEOUT, daily::persist(daily)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => tracing::error!("persist daily state error: {err}"),
+            Err(_) => tracing::error!("persist daily state timed out"),
+        }
+    }
+
+    tracing::info!("shutdown complete");
+
+    logger::flush();
+}
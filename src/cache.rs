@@ -0,0 +1,92 @@
+use crate::metrics;
+use lru::LruCache;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+struct Entry<V> {
+    cell: Arc<OnceCell<Arc<V>>>,
+    created_at: Instant,
+}
+
+/// 容量とTTLで上限を設けた非同期キャッシュ
+///
+/// 同じキーへの同時リクエストは1回の取得にまとめられる
+pub struct TtlCache<K, V> {
+    /// メトリクスのラベル
+    name: &'static str,
+    entries: Mutex<LruCache<K, Entry<V>>>,
+    ttl: Duration,
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: Hash + Eq + Clone,
+{
+    pub fn new(name: &'static str, capacity: NonZeroUsize, ttl: Duration) -> Self {
+        Self {
+            name,
+            entries: Mutex::new(LruCache::new(capacity)),
+            ttl,
+        }
+    }
+
+    pub async fn get_or_try_insert_with<F, Fut, E>(&self, key: K, init: F) -> Result<Arc<V>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        let cell = {
+            let mut entries = self.entries.lock().expect("failed to lock");
+
+            match entries.get(&key) {
+                Some(entry) if entry.created_at.elapsed() < self.ttl => {
+                    metrics::inc(
+                        "bot_cache_requests_total",
+                        &[("cache", self.name), ("result", "hit")],
+                    );
+
+                    Arc::clone(&entry.cell)
+                }
+                _ => {
+                    metrics::inc(
+                        "bot_cache_requests_total",
+                        &[("cache", self.name), ("result", "miss")],
+                    );
+
+                    let cell = Arc::new(OnceCell::new());
+
+                    entries.put(
+                        key.clone(),
+                        Entry {
+                            cell: Arc::clone(&cell),
+                            created_at: Instant::now(),
+                        },
+                    );
+
+                    cell
+                }
+            }
+        };
+
+        let result = cell
+            .get_or_try_init(|| async { init().await.map(Arc::new) })
+            .await
+            .cloned();
+
+        if result.is_err() {
+            let mut entries = self.entries.lock().expect("failed to lock");
+
+            if entries
+                .peek(&key)
+                .is_some_and(|entry| Arc::ptr_eq(&entry.cell, &cell))
+            {
+                entries.pop(&key);
+            }
+        }
+
+        result
+    }
+}
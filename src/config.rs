@@ -1,5 +1,6 @@
 use poise::serenity_prelude as serenity;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::{fs, time::Duration};
 
 pub type AnyError = Box<dyn std::error::Error + Send + Sync>;
@@ -10,6 +11,17 @@ pub struct Config {
     pub verify: Verify,
     pub pokemon: Pokemon,
     pub greeter: Greeter,
+    pub daily: Option<Daily>,
+    #[serde(default)]
+    pub proxy: Proxy,
+    #[serde(default)]
+    pub registration: Registration,
+    pub monitor: Option<Monitor>,
+    #[serde(default)]
+    pub logging: Logging,
+    /// コマンド名（サブコマンドは`dareda start`のように空白区切り）ごとのクールダウン
+    #[serde(default)]
+    pub cooldowns: HashMap<String, Cooldown>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -36,6 +48,200 @@ pub struct Pokemon {
     pub time_limit: Duration,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct Daily {
+    pub channel_id: serenity::ChannelId,
+    /// 出題時刻（HH:MM）
+    pub post_time: String,
+    pub utc_offset_hours: i64,
+    pub state_path: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Proxy {
+    pub sources: Vec<ProxySource>,
+    /// 受け取ったリクエストヘッダーをそのまま返すHTTPのURL
+    pub judge_url: String,
+    #[serde(with = "humantime_serde")]
+    pub check_timeout: Duration,
+    pub check_concurrency: usize,
+    /// プールを再チェックする間隔
+    #[serde(with = "humantime_serde")]
+    pub refresh_interval: Duration,
+    /// 1回の更新で新しくチェックする候補の最大数
+    pub refresh_batch: usize,
+    /// これ以上連続で失敗したらプールから外す
+    pub max_failures: u32,
+    /// 最後のチェックからこれ以上経ったものは返さない
+    #[serde(with = "humantime_serde")]
+    pub max_age: Duration,
+}
+
+impl Default for Proxy {
+    fn default() -> Self {
+        Self {
+            sources: vec![ProxySource::Proxyscrape],
+            judge_url: "http://azenv.net/".to_owned(),
+            check_timeout: Duration::from_secs(5),
+            check_concurrency: 32,
+            refresh_interval: Duration::from_secs(10 * 60),
+            refresh_batch: 500,
+            max_failures: 3,
+            max_age: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProxySource {
+    Proxyscrape,
+    /// 1行に1つ`ip:port`が書かれたテキスト
+    Url {
+        url: String,
+    },
+    Github {
+        repo: String,
+        branch: String,
+        path: String,
+    },
+    File {
+        path: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    Weekly,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Pretty,
+    /// 1行に1つのJSON
+    Json,
+}
+
+/// ログの出力先とレベル。環境変数`RUST_LOG`があればレベルはそちらを使う
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Logging {
+    pub dir: String,
+    pub file_name: String,
+    pub rotation: LogRotation,
+    /// 残すファイルの数。なければ消さない
+    pub max_files: Option<usize>,
+    /// ファイルの形式
+    pub format: LogFormat,
+    pub console_format: LogFormat,
+    /// このボットのレベル。なければデバッグビルドでtrace、リリースビルドでファイルはdebug・コンソールはinfo
+    pub file_level: Option<String>,
+    pub console_level: Option<String>,
+    /// ほかのクレートのレベル
+    pub default_level: String,
+    /// ターゲットごとのレベル（`serenity = "info"`など）
+    pub targets: HashMap<String, String>,
+    /// WARN以上のログを送るチャンネル
+    pub discord: Option<DiscordLog>,
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Self {
+            dir: "logs".to_owned(),
+            file_name: "app.log".to_owned(),
+            rotation: LogRotation::Daily,
+            max_files: None,
+            format: LogFormat::Pretty,
+            console_format: LogFormat::Pretty,
+            file_level: None,
+            console_level: None,
+            default_level: "warn".to_owned(),
+            targets: HashMap::new(),
+            discord: None,
+        }
+    }
+}
+
+fn default_discord_log_level() -> String {
+    "warn".to_owned()
+}
+
+fn default_discord_log_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_discord_log_dedup_window() -> Duration {
+    Duration::from_secs(10 * 60)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiscordLog {
+    pub channel_id: serenity::ChannelId,
+    /// `warn`か`error`
+    #[serde(default = "default_discord_log_level")]
+    pub level: String,
+    /// まとめて送る間隔。1回に送るメッセージは1つまで
+    #[serde(with = "humantime_serde", default = "default_discord_log_interval")]
+    pub interval: Duration,
+    /// 同じ内容のログは、この間は送らずに回数だけ数える
+    #[serde(with = "humantime_serde", default = "default_discord_log_dedup_window")]
+    pub dedup_window: Duration,
+}
+
+/// `/healthz`と`/metrics`を返すHTTPサーバー
+#[derive(Debug, Clone, Deserialize)]
+pub struct Monitor {
+    pub listen: std::net::SocketAddr,
+}
+
+/// スラッシュコマンドの登録先
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationMode {
+    /// `guilds`のサーバーだけ。すぐに反映される
+    #[default]
+    Guild,
+    /// すべてのサーバー。使わない登録先のコマンドは消す
+    Global,
+    /// グローバルと`guilds`の両方。`guilds`ではコマンドが2つずつ表示される
+    Both,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Registration {
+    pub mode: RegistrationMode,
+    /// サーバーごとに登録する先。空なら`guild.guild_id`
+    pub guilds: Vec<serenity::GuildId>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CooldownScope {
+    User,
+    Channel,
+    Guild,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Cooldown {
+    pub scope: CooldownScope,
+    /// `per`の間に実行できる回数
+    pub limit: usize,
+    #[serde(with = "humantime_serde")]
+    pub per: Duration,
+}
+
 impl Config {
     pub fn load() -> Result<Self, AnyError> {
         let text = fs::read_to_string("config.toml")?;
@@ -0,0 +1,218 @@
+use crate::i18n::{Locale, t};
+use crate::{Data, metrics};
+use poise::serenity_prelude as serenity;
+use rand::Rng;
+use std::fmt;
+use std::sync::atomic::Ordering;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// 利用者の入力や操作が原因。メッセージはそのまま表示する
+    User(String),
+    /// Discordや外部APIとの通信の失敗
+    Upstream(BoxError),
+    /// 想定していない失敗（バグ）
+    Internal(BoxError),
+}
+
+impl Error {
+    pub fn user(message: impl Into<String>) -> Self {
+        Error::User(message.into())
+    }
+
+    /// 利用者に表示するメッセージ
+    fn message(&self, id: &str, locale: Locale) -> String {
+        match self {
+            Error::User(message) => message.clone(),
+            Error::Upstream(_) => t!(locale, "error.upstream", id = id),
+            Error::Internal(_) => t!(locale, "error.internal", id = id),
+        }
+    }
+
+    /// メトリクスのラベル
+    fn kind(&self) -> &'static str {
+        match self {
+            Error::User(_) => "user",
+            Error::Upstream(_) => "upstream",
+            Error::Internal(_) => "internal",
+        }
+    }
+
+    fn log(&self, id: &str, context: &str) {
+        match self {
+            Error::User(message) => {
+                tracing::debug!(error_id = id, "user error in {context}: {message}")
+            }
+            Error::Upstream(err) => {
+                tracing::warn!(error_id = id, "upstream error in {context}: {err:?}")
+            }
+            Error::Internal(err) => {
+                tracing::error!(error_id = id, "internal error in {context}: {err:?}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::User(message) => f.write_str(message),
+            Error::Upstream(err) => write!(f, "upstream: {err}"),
+            Error::Internal(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($variant:ident: $($t:ty),+ $(,)?) => {
+        $(
+            impl From<$t> for Error {
+                fn from(err: $t) -> Self {
+                    Error::$variant(err.into())
+                }
+            }
+        )+
+    };
+}
+
+impl_from!(Upstream: serenity::Error, reqwest::Error);
+impl_from!(
+    Internal: &str,
+    String,
+    BoxError,
+    std::io::Error,
+    serde_json::Error,
+    image::ImageError,
+    std::num::TryFromIntError,
+    tokio::task::JoinError,
+);
+
+/// ログと利用者への表示で共通のID
+fn new_id() -> String {
+    format!("{:08X}", rand::thread_rng().r#gen::<u32>())
+}
+
+fn error_reply(content: String) -> serenity::CreateInteractionResponse {
+    serenity::CreateInteractionResponse::Message(
+        serenity::CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true),
+    )
+}
+
+fn error_followup(content: String) -> serenity::CreateInteractionResponseFollowup {
+    serenity::CreateInteractionResponseFollowup::new()
+        .content(content)
+        .ephemeral(true)
+}
+
+/// ボタンやモーダルの処理で起きたエラーを操作した人に知らせる
+async fn reply_to_interaction(
+    ctx: &serenity::Context,
+    interaction: &serenity::Interaction,
+    error: &Error,
+    id: &str,
+) -> Result<(), serenity::Error> {
+    match interaction {
+        serenity::Interaction::Component(i) => {
+            let content = error.message(id, Locale::from_discord(&i.locale));
+
+            if i.create_response(ctx, error_reply(content.clone()))
+                .await
+                .is_err()
+            {
+                i.create_followup(ctx, error_followup(content)).await?;
+            }
+        }
+        serenity::Interaction::Modal(i) => {
+            let content = error.message(id, Locale::from_discord(&i.locale));
+
+            if i.create_response(ctx, error_reply(content.clone()))
+                .await
+                .is_err()
+            {
+                i.create_followup(ctx, error_followup(content)).await?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// 公開の`defer()`で考え中のままの応答を消す。残っていると次のフォローアップがそれを置き換え、
+/// `ephemeral`が無視されてエラーが公開されてしまう
+async fn discard_deferred(ctx: crate::Context<'_>) -> Result<(), serenity::Error> {
+    let poise::Context::Application(app) = ctx else {
+        return Ok(());
+    };
+
+    if !app.has_sent_initial_response.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    let response = app.interaction.get_response(ctx.http()).await?;
+
+    if response.flags.is_some_and(|f| {
+        f.contains(serenity::MessageFlags::LOADING)
+            && !f.contains(serenity::MessageFlags::EPHEMERAL)
+    }) {
+        app.interaction.delete_response(ctx.http()).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn on_error(err: poise::FrameworkError<'_, Data, Error>) {
+    match err {
+        poise::FrameworkError::Command { error, ctx, .. } => {
+            let id = new_id();
+
+            metrics::command_failed(ctx.id(), &ctx.command().qualified_name, error.kind());
+
+            error.log(
+                &id,
+                &format!(
+                    "/{} (user: {}, channel: {})",
+                    ctx.command().qualified_name,
+                    ctx.author().id,
+                    ctx.channel_id()
+                ),
+            );
+
+            if let Err(err) = discard_deferred(ctx).await {
+                tracing::debug!(error_id = id, "failed to discard deferred response: {err}");
+            }
+
+            let reply = poise::CreateReply::default()
+                .content(error.message(&id, Locale::of(ctx)))
+                .ephemeral(true);
+
+            if let Err(err) = ctx.send(reply).await {
+                tracing::warn!(error_id = id, "failed to send error reply: {err}");
+            }
+        }
+        poise::FrameworkError::EventHandler {
+            error, ctx, event, ..
+        } => {
+            let id = new_id();
+
+            error.log(&id, &format!("event {}", event.snake_case_name()));
+
+            if let serenity::FullEvent::InteractionCreate { interaction } = event
+                && let Err(err) = reply_to_interaction(ctx, interaction, &error, &id).await
+            {
+                tracing::warn!(error_id = id, "failed to send error reply: {err}");
+            }
+        }
+        // クールダウンなどで止めた場合はチェック側で返信している
+        poise::FrameworkError::CommandCheckFailed { error: None, .. } => {}
+        other => {
+            if let Err(err) = poise::builtins::on_error(other).await {
+                tracing::warn!("Global error: {err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod command;
+mod common;
+pub mod handler;
+pub mod index;
+mod render;
@@ -0,0 +1,75 @@
+use crate::bot::check::is_staff;
+use crate::bot::component::route_autocomplete;
+use crate::help::index::CommandHelp;
+use crate::help::render;
+use crate::i18n::{Locale, t};
+use crate::{Context, Error};
+use poise::serenity_prelude as serenity;
+
+async fn author_permissions(ctx: Context<'_>) -> serenity::Permissions {
+    ctx.author_member()
+        .await
+        .and_then(|m| m.permissions)
+        .unwrap_or_default()
+}
+
+/// 実行した人が使えるコマンド
+async fn visible_commands(ctx: Context<'_>) -> Vec<&CommandHelp> {
+    let permissions = author_permissions(ctx).await;
+    let is_staff = is_staff(ctx).await.unwrap_or(false);
+
+    ctx.data()
+        .help
+        .iter()
+        .filter(|c| c.visible_to(permissions, is_staff))
+        .collect()
+}
+
+pub async fn autocomplete_command(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<serenity::AutocompleteChoice> {
+    let partial = partial.trim_start_matches('/').to_lowercase();
+
+    visible_commands(ctx)
+        .await
+        .into_iter()
+        .map(|c| c.qualified_name.clone())
+        .filter(|name| name.contains(&partial))
+        .take(25)
+        .map(serenity::AutocompleteChoice::from)
+        .collect()
+}
+
+/// コマンドの一覧と使い方を表示します
+#[poise::command(slash_command, guild_only)]
+pub async fn help(
+    ctx: Context<'_>,
+    #[description = "詳しく見たいコマンド"]
+    #[autocomplete = "route_autocomplete"]
+    command: Option<String>,
+) -> Result<(), Error> {
+    let locale = Locale::of(ctx);
+    let cooldowns = &ctx.data().config.cooldowns;
+    let commands = visible_commands(ctx).await;
+
+    let reply = match command {
+        Some(name) => {
+            let name = name.trim().trim_start_matches('/');
+            let Some(command) = commands.iter().find(|c| c.qualified_name == name) else {
+                return Err(Error::user(t!(locale, "help.not_found", name = name)));
+            };
+
+            poise::CreateReply::default().embed(render::detail(command, cooldowns, locale))
+        }
+        None => {
+            let (embed, rows) = render::page(&commands, 0, cooldowns, locale)?;
+
+            poise::CreateReply::default().embed(embed).components(rows)
+        }
+    };
+
+    ctx.send(reply.ephemeral(true)).await?;
+
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use crate::bot::component::ComponentId;
+
+pub const COLOR_HELP: u32 = 0x8FD3FF;
+
+/// 1ページに並べるコマンドの数
+pub const COMMANDS_PER_PAGE: usize = 8;
+
+pub enum HelpComponent {
+    /// 一覧のページ番号（0始まり）
+    Page(usize),
+}
+
+impl ComponentId for HelpComponent {
+    const NAMESPACE: &'static str = "help";
+
+    fn fields(&self) -> Vec<String> {
+        match self {
+            HelpComponent::Page(page) => vec!["page".to_owned(), page.to_string()],
+        }
+    }
+
+    fn parse(fields: &[&str]) -> Option<Self> {
+        match fields {
+            ["page", page] => page.parse().ok().map(HelpComponent::Page),
+            _ => None,
+        }
+    }
+}
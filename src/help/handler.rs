@@ -0,0 +1,46 @@
+use crate::help::common::HelpComponent;
+use crate::help::render;
+use crate::i18n::Locale;
+use crate::{Data, Error};
+use poise::serenity_prelude as serenity;
+
+pub async fn handle_component(
+    ctx: &serenity::Context,
+    data: &Data,
+    i: &serenity::ComponentInteraction,
+    id: HelpComponent,
+) -> Result<(), Error> {
+    let HelpComponent::Page(page) = id;
+    let permissions = i
+        .member
+        .as_ref()
+        .and_then(|m| m.permissions)
+        .unwrap_or_default();
+    let is_staff = i
+        .member
+        .as_ref()
+        .is_some_and(|m| m.roles.contains(&data.config.guild.staff_role_id));
+    let commands: Vec<_> = data
+        .help
+        .iter()
+        .filter(|c| c.visible_to(permissions, is_staff))
+        .collect();
+    let (embed, rows) = render::page(
+        &commands,
+        page,
+        &data.config.cooldowns,
+        Locale::from_discord(&i.locale),
+    )?;
+
+    i.create_response(
+        ctx,
+        serenity::CreateInteractionResponse::UpdateMessage(
+            serenity::CreateInteractionResponseMessage::new()
+                .embed(embed)
+                .components(rows),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use crate::bot::check::StaffOnly;
+use crate::i18n::Locale;
+use crate::{Data, Error};
+use poise::serenity_prelude as serenity;
+
+/// 英語の説明として使うDiscordのロケール
+const ENGLISH_LOCALE: &str = "en-US";
+
+pub struct ParamHelp {
+    pub name: String,
+    description: Option<String>,
+    description_en: Option<String>,
+    pub required: bool,
+}
+
+impl ParamHelp {
+    pub fn description(&self, locale: Locale) -> Option<&str> {
+        localized(&self.description, &self.description_en, locale)
+    }
+}
+
+/// ヘルプに載せるコマンドの情報。起動時に`framework.options().commands`から作る
+pub struct CommandHelp {
+    /// サブコマンドは`dareda start`のように空白区切り
+    pub qualified_name: String,
+    pub category: Option<String>,
+    description: Option<String>,
+    description_en: Option<String>,
+    pub params: Vec<ParamHelp>,
+    /// 実行に必要な権限
+    pub permissions: serenity::Permissions,
+    /// スタッフロールを持つメンバーだけが使える
+    staff_only: bool,
+    hidden: bool,
+}
+
+impl CommandHelp {
+    pub fn description(&self, locale: Locale) -> Option<&str> {
+        localized(&self.description, &self.description_en, locale)
+    }
+
+    /// `permissions`を持つメンバーが使えるか
+    pub fn visible_to(&self, permissions: serenity::Permissions, is_staff: bool) -> bool {
+        !self.hidden
+            && (!self.staff_only || is_staff)
+            && (permissions.administrator() || permissions.contains(self.permissions))
+    }
+}
+
+fn localized<'a>(
+    ja: &'a Option<String>,
+    en: &'a Option<String>,
+    locale: Locale,
+) -> Option<&'a str> {
+    match locale {
+        Locale::Ja => ja.as_deref(),
+        Locale::En => en.as_deref().or(ja.as_deref()),
+    }
+}
+
+/// サブコマンドを持つコマンドは、実行できるサブコマンドだけを載せる
+fn collect(
+    command: &poise::Command<Data, Error>,
+    root: &poise::Command<Data, Error>,
+    out: &mut Vec<CommandHelp>,
+) {
+    if !command.subcommands.is_empty() {
+        for subcommand in &command.subcommands {
+            collect(subcommand, root, out);
+        }
+
+        return;
+    }
+
+    out.push(CommandHelp {
+        qualified_name: command.qualified_name.clone(),
+        category: command.category.clone().or(root.category.clone()),
+        description: command.description.clone(),
+        description_en: command
+            .description_localizations
+            .get(ENGLISH_LOCALE)
+            .cloned(),
+        params: command
+            .parameters
+            .iter()
+            .map(|p| ParamHelp {
+                name: p.name.clone(),
+                description: p.description.clone(),
+                description_en: p.description_localizations.get(ENGLISH_LOCALE).cloned(),
+                required: p.required,
+            })
+            .collect(),
+        // Discordが見るのは親コマンドの`default_member_permissions`だけ
+        permissions: root.default_member_permissions | command.required_permissions,
+        staff_only: command.custom_data.is::<StaffOnly>() || root.custom_data.is::<StaffOnly>(),
+        hidden: command.hide_in_help || command.owners_only || root.owners_only,
+    });
+}
+
+pub fn build(commands: &[poise::Command<Data, Error>]) -> Vec<CommandHelp> {
+    let mut index = Vec::new();
+
+    for command in commands
+        .iter()
+        .filter(|c| c.slash_action.is_some() || !c.subcommands.is_empty())
+    {
+        collect(command, command, &mut index);
+    }
+
+    index
+}
@@ -0,0 +1,174 @@
+use crate::Error;
+use crate::bot::component::ComponentId;
+use crate::config::{Cooldown, CooldownScope};
+use crate::help::common::{COLOR_HELP, COMMANDS_PER_PAGE, HelpComponent};
+use crate::help::index::CommandHelp;
+use crate::i18n::{Locale, t};
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+
+fn category_label(category: Option<&str>, locale: Locale) -> String {
+    category.map_or_else(|| t!(locale, "help.general"), str::to_owned)
+}
+
+fn cooldown_label(cooldown: &Cooldown, locale: Locale) -> String {
+    let scope = match cooldown.scope {
+        CooldownScope::User => t!(locale, "help.scope.user"),
+        CooldownScope::Channel => t!(locale, "help.scope.channel"),
+        CooldownScope::Guild => t!(locale, "help.scope.guild"),
+    };
+
+    t!(
+        locale,
+        "help.cooldown_value",
+        per = cooldown.per.as_secs(),
+        limit = cooldown.limit,
+        scope = scope
+    )
+}
+
+/// カテゴリごとにまとめて、多いカテゴリは複数ページに分ける。カテゴリなしは先頭
+fn paginate<'a>(commands: &[&'a CommandHelp]) -> Vec<(Option<&'a str>, Vec<&'a CommandHelp>)> {
+    let mut categories: Vec<(Option<&str>, Vec<&CommandHelp>)> = Vec::new();
+
+    for command in commands {
+        let category = command.category.as_deref();
+
+        match categories.iter_mut().find(|(c, _)| *c == category) {
+            Some((_, list)) => list.push(command),
+            None => categories.push((category, vec![command])),
+        }
+    }
+
+    categories.sort_by_key(|(c, _)| c.is_some());
+
+    categories
+        .into_iter()
+        .flat_map(|(category, list)| {
+            list.chunks(COMMANDS_PER_PAGE)
+                .map(|chunk| (category, chunk.to_vec()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// 一覧の`page`ページ目。範囲外なら最後のページにする
+pub fn page(
+    commands: &[&CommandHelp],
+    page: usize,
+    cooldowns: &HashMap<String, Cooldown>,
+    locale: Locale,
+) -> Result<(serenity::CreateEmbed, Vec<serenity::CreateActionRow>), Error> {
+    let pages = paginate(commands);
+
+    let Some(last) = pages.len().checked_sub(1) else {
+        let embed = serenity::CreateEmbed::new()
+            .color(COLOR_HELP)
+            .title(t!(locale, "help.title"))
+            .description(t!(locale, "help.empty"));
+
+        return Ok((embed, Vec::new()));
+    };
+
+    let page = page.min(last);
+    let (category, list) = &pages[page];
+    let mut embed = serenity::CreateEmbed::new()
+        .color(COLOR_HELP)
+        .title(format!(
+            "{} — {}",
+            t!(locale, "help.title"),
+            category_label(*category, locale)
+        ))
+        .description(t!(locale, "help.hint"))
+        .footer(serenity::CreateEmbedFooter::new(t!(
+            locale,
+            "help.page",
+            current = page + 1,
+            total = pages.len()
+        )));
+
+    for command in list {
+        let mut value = command
+            .description(locale)
+            .map_or_else(|| t!(locale, "help.no_description"), str::to_owned);
+
+        if let Some(cooldown) = cooldowns.get(&command.qualified_name) {
+            value += &format!("\n⏱ {}", cooldown_label(cooldown, locale));
+        }
+
+        embed = embed.field(format!("/{}", command.qualified_name), value, false);
+    }
+
+    if pages.len() == 1 {
+        return Ok((embed, Vec::new()));
+    }
+
+    let row = serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(HelpComponent::Page(page.saturating_sub(1)).encode()?)
+            .label(t!(locale, "help.prev"))
+            .style(serenity::ButtonStyle::Secondary)
+            .disabled(page == 0),
+        serenity::CreateButton::new(HelpComponent::Page(page + 1).encode()?)
+            .label(t!(locale, "help.next"))
+            .style(serenity::ButtonStyle::Secondary)
+            .disabled(page == last),
+    ]);
+
+    Ok((embed, vec![row]))
+}
+
+/// 1つのコマンドの詳細
+pub fn detail(
+    command: &CommandHelp,
+    cooldowns: &HashMap<String, Cooldown>,
+    locale: Locale,
+) -> serenity::CreateEmbed {
+    let none = || t!(locale, "help.none");
+    let params = if command.params.is_empty() {
+        none()
+    } else {
+        command
+            .params
+            .iter()
+            .map(|p| {
+                format!(
+                    "`{}`（{}）{}",
+                    p.name,
+                    if p.required {
+                        t!(locale, "help.required")
+                    } else {
+                        t!(locale, "help.optional")
+                    },
+                    p.description(locale)
+                        .map_or("".to_owned(), |d| format!(": {d}"))
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let permissions = if command.permissions.is_empty() {
+        none()
+    } else {
+        command.permissions.get_permission_names().join(", ")
+    };
+    let cooldown = cooldowns
+        .get(&command.qualified_name)
+        .map_or_else(none, |c| cooldown_label(c, locale));
+
+    serenity::CreateEmbed::new()
+        .color(COLOR_HELP)
+        .title(format!("/{}", command.qualified_name))
+        .description(
+            command
+                .description(locale)
+                .map_or_else(|| t!(locale, "help.no_description"), str::to_owned),
+        )
+        .field(t!(locale, "help.params"), params, false)
+        .field(t!(locale, "help.permissions"), permissions, true)
+        .field(t!(locale, "help.cooldown"), cooldown, true)
+        .field(
+            t!(locale, "help.category"),
+            category_label(command.category.as_deref(), locale),
+            true,
+        )
+}
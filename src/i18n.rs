@@ -0,0 +1,217 @@
+use crate::{Context, Data, Error};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::LazyLock;
+
+/// 英語の説明を登録するDiscordのロケール
+const ENGLISH_LOCALES: &[&str] = &["en-US", "en-GB"];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Locale {
+    #[default]
+    Ja,
+    En,
+}
+
+impl Locale {
+    /// Discordのロケール（`ja`、`en-US`など）から選ぶ。日本語以外は英語にする
+    pub fn from_discord(locale: &str) -> Self {
+        if locale.starts_with("ja") {
+            Locale::Ja
+        } else {
+            Locale::En
+        }
+    }
+
+    /// コマンドを実行した人のロケール。プレフィックスコマンドでは日本語
+    pub fn of(ctx: Context<'_>) -> Self {
+        match ctx {
+            poise::Context::Application(actx) => Self::from_discord(&actx.interaction.locale),
+            poise::Context::Prefix(_) => Self::default(),
+        }
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            Locale::Ja => include_str!("../locales/ja.toml"),
+            Locale::En => include_str!("../locales/en.toml"),
+        }
+    }
+}
+
+fn flatten(prefix: &str, table: toml::Table, out: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{prefix}.{key}")
+        };
+
+        match value {
+            toml::Value::String(s) => {
+                out.insert(key, s);
+            }
+            toml::Value::Table(t) => flatten(&key, t, out),
+            _ => tracing::warn!("ignored non-string message: {key}"),
+        }
+    }
+}
+
+static CATALOGS: LazyLock<HashMap<Locale, HashMap<String, String>>> = LazyLock::new(|| {
+    [Locale::Ja, Locale::En]
+        .into_iter()
+        .map(|locale| {
+            let mut messages = HashMap::new();
+
+            match locale.source().parse::<toml::Table>() {
+                Ok(table) => flatten("", table, &mut messages),
+                Err(err) => tracing::error!("invalid message catalog {locale:?}: {err}"),
+            }
+
+            (locale, messages)
+        })
+        .collect()
+});
+
+/// メッセージを取り出す。英語になければ日本語を使う
+pub fn get(locale: Locale, key: &str) -> Option<&'static str> {
+    CATALOGS
+        .get(&locale)
+        .and_then(|m| m.get(key))
+        .or_else(|| CATALOGS.get(&Locale::Ja).and_then(|m| m.get(key)))
+        .map(|s| s.as_str())
+}
+
+/// メッセージを取り出して`{name}`を置き換える。見つからなければキーを返す
+pub fn format(locale: Locale, key: &str, args: &[(&str, &(dyn Display + Sync))]) -> String {
+    let Some(message) = get(locale, key) else {
+        tracing::warn!("missing message: {key}");
+
+        return key.to_owned();
+    };
+
+    args.iter()
+        .fold(message.to_owned(), |message, (name, value)| {
+            message.replace(&format!("{{{name}}}"), &value.to_string())
+        })
+}
+
+/// `t!(locale, "key")`または`t!(locale, "key", name = value, ...)`
+macro_rules! t {
+    ($locale:expr, $key:expr) => {
+        $crate::i18n::format($locale, $key, &[])
+    };
+    ($locale:expr, $key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::format(
+            $locale,
+            $key,
+            &[$((stringify!($name), &$value as &(dyn std::fmt::Display + Sync))),+],
+        )
+    };
+}
+
+pub(crate) use t;
+
+/// カタログのキー。`commands.{コマンド名}`で、サブコマンドは`commands.{親}_{子}`
+///
+/// `qualified_name`はフレームワークの初期化まで設定されないので、親のキーから作る
+fn command_key(parent: Option<&str>, command: &poise::Command<Data, Error>) -> String {
+    match parent {
+        Some(parent) => format!("{parent}_{}", command.name),
+        None => format!("commands.{}", command.name),
+    }
+}
+
+/// コマンド名・説明・引数の説明に英語を登録する
+pub fn localize_commands(commands: &mut [poise::Command<Data, Error>]) {
+    localize(commands, None);
+}
+
+fn localize(commands: &mut [poise::Command<Data, Error>], parent: Option<&str>) {
+    for command in commands {
+        let key = command_key(parent, command);
+
+        for locale in ENGLISH_LOCALES {
+            if let Some(name) = lookup_exact(Locale::En, &format!("{key}.name")) {
+                command
+                    .name_localizations
+                    .insert(locale.to_string(), name.to_owned());
+            }
+            if let Some(description) = lookup_exact(Locale::En, &format!("{key}.description")) {
+                command
+                    .description_localizations
+                    .insert(locale.to_string(), description.to_owned());
+            }
+
+            for parameter in &mut command.parameters {
+                if let Some(description) =
+                    lookup_exact(Locale::En, &format!("{key}.params.{}", parameter.name))
+                {
+                    parameter
+                        .description_localizations
+                        .insert(locale.to_string(), description.to_owned());
+                }
+            }
+        }
+
+        localize(&mut command.subcommands, Some(&key));
+    }
+}
+
+/// 日本語へのフォールバックなしで探す
+fn lookup_exact(locale: Locale, key: &str) -> Option<&'static str> {
+    CATALOGS
+        .get(&locale)
+        .and_then(|m| m.get(key))
+        .map(|s| s.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use poise::serenity_prelude as serenity;
+    use std::collections::HashSet;
+
+    fn keys(
+        commands: &[poise::Command<Data, Error>],
+        parent: Option<&str>,
+        out: &mut HashSet<String>,
+    ) {
+        for command in commands {
+            let key = command_key(parent, command);
+
+            out.insert(format!("{key}.name"));
+            out.insert(format!("{key}.description"));
+            out.extend(
+                command
+                    .parameters
+                    .iter()
+                    .map(|p| format!("{key}.params.{}", p.name)),
+            );
+
+            keys(&command.subcommands, Some(&key), out);
+        }
+    }
+
+    #[test]
+    fn every_command_key_is_used() {
+        let commands = crate::commands(serenity::Permissions::empty());
+        let mut used = HashSet::new();
+
+        keys(&commands, None, &mut used);
+
+        for (locale, messages) in CATALOGS.iter() {
+            for key in messages.keys().filter(|k| k.starts_with("commands.")) {
+                assert!(used.contains(key), "unused key in {locale:?}: {key}");
+            }
+        }
+
+        let start = commands
+            .iter()
+            .find(|c| c.name == "dareda")
+            .and_then(|c| c.subcommands.iter().find(|c| c.name == "start"))
+            .unwrap();
+
+        assert!(start.description_localizations.contains_key("en-US"));
+    }
+}
@@ -1,41 +1,115 @@
+mod discord;
+mod framework;
+mod json;
+
 use crate::Error;
+use crate::config::{LogFormat, LogRotation, Logging};
 use std::sync::{LazyLock, Mutex};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::{EnvFilter, Layer, Registry, fmt};
 
+pub use discord::spawn_forwarder;
+pub use framework::Traced;
+
 static LOG_GUARD: LazyLock<Mutex<Option<tracing_appender::non_blocking::WorkerGuard>>> =
     LazyLock::new(Default::default);
 
-pub fn init_tracing_subscriber() -> Result<(), Error> {
-    let file_appender = tracing_appender::rolling::daily("logs", "app.log");
-    let (file_writer, guard) = tracing_appender::non_blocking(file_appender);
+type BoxLayer = Box<dyn Layer<Registry> + Send + Sync>;
 
-    *LOG_GUARD.lock().expect("failed to lock") = Some(guard);
+/// `RUST_LOG`があればそれを、なければ設定からフィルターを作る
+fn filter(config: &Logging, level: Option<&str>, default_level: &str) -> EnvFilter {
+    if let Ok(filter) = EnvFilter::try_from_default_env() {
+        return filter;
+    }
 
     let crate_name = env!("CARGO_CRATE_NAME");
+    let directives = std::iter::once(config.default_level.clone())
+        .chain(std::iter::once(format!(
+            "{crate_name}={}",
+            level.unwrap_or(default_level)
+        )))
+        .chain(config.targets.iter().map(|(t, l)| format!("{t}={l}")))
+        .collect::<Vec<_>>()
+        .join(",");
 
-    #[cfg(debug_assertions)]
-    let file_layer_filter = format!("{crate_name}=trace,warn");
-    #[cfg(not(debug_assertions))]
-    let file_layer_filter = format!("{crate_name}=debug,warn");
+    EnvFilter::new(directives)
+}
+
+fn layer<W>(format: LogFormat, writer: W, ansi: bool) -> BoxLayer
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Pretty => fmt::Layer::default()
+            .with_writer(writer)
+            .with_ansi(ansi)
+            .boxed(),
+        LogFormat::Json => fmt::Layer::default()
+            .with_writer(writer)
+            .fmt_fields(json::JsonFields)
+            .event_format(json::JsonFormat)
+            .boxed(),
+    }
+}
+
+pub fn init_tracing_subscriber(config: &Logging) -> Result<(), Error> {
+    let rotation = match config.rotation {
+        LogRotation::Minutely => Rotation::MINUTELY,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Weekly => Rotation::WEEKLY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(&config.file_name);
+
+    if let Some(max_files) = config.max_files {
+        builder = builder.max_log_files(max_files);
+    }
+
+    let file_appender = builder
+        .build(&config.dir)
+        .map_err(|e| Error::Internal(e.into()))?;
+    let (file_writer, guard) = tracing_appender::non_blocking(file_appender);
+
+    *LOG_GUARD.lock().expect("failed to lock") = Some(guard);
 
     #[cfg(debug_assertions)]
-    let console_layer_filter = format!("{crate_name}=trace,warn");
+    let (file_default, console_default) = ("trace", "trace");
     #[cfg(not(debug_assertions))]
-    let console_layer_filter = format!("{crate_name}=info,warn");
+    let (file_default, console_default) = ("debug", "info");
+
+    let mut layers: Vec<BoxLayer> = vec![
+        layer(config.format, file_writer, false)
+            .with_filter(filter(config, config.file_level.as_deref(), file_default))
+            .boxed(),
+        layer(config.console_format, std::io::stdout, true)
+            .with_filter(filter(
+                config,
+                config.console_level.as_deref(),
+                console_default,
+            ))
+            .boxed(),
+    ];
 
-    let file_layer = fmt::Layer::default()
-        .with_writer(file_writer)
-        .with_ansi(false)
-        .with_filter(EnvFilter::new(&file_layer_filter));
+    if let Some(discord) = &config.discord {
+        let layer = discord::DiscordLayer::new(discord);
+        let filter = layer.filter();
 
-    let console_layer = fmt::Layer::default()
-        .with_writer(std::io::stdout)
-        .with_filter(EnvFilter::new(&console_layer_filter));
+        layers.push(layer.with_filter(filter).boxed());
+    }
 
-    let subscriber = Registry::default().with(file_layer).with(console_layer);
+    let subscriber = Registry::default().with(layers);
 
-    tracing::subscriber::set_global_default(subscriber)?;
+    tracing::subscriber::set_global_default(subscriber).map_err(|e| Error::Internal(e.into()))?;
 
     Ok(())
 }
+
+/// ファイルに書き残したログを書き出す。これ以降のログはファイルには残らない
+pub fn flush() {
+    drop(LOG_GUARD.lock().expect("failed to lock").take());
+}
@@ -0,0 +1,322 @@
+use crate::config::DiscordLog;
+use crate::i18n::{Locale, t};
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Instrument, Level, Metadata, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::FilterFn;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+const CHANNEL_LOCALE: Locale = Locale::Ja;
+
+/// 送信待ちとしてためておく最大数。超えた分は捨てる
+const QUEUE_SIZE: usize = 1024;
+
+/// 1つのメッセージに付けられる埋め込みの最大数
+const MAX_EMBEDS: usize = 10;
+
+const MAX_DESCRIPTION_CHARS: usize = 4000;
+
+/// このスパンの中のログは送らない。送信の失敗がまた送られるのを防ぐ
+const FORWARDER_SPAN: &str = "discord_log";
+
+const COLOR_ERROR: u32 = 0xE74C3C;
+const COLOR_WARN: u32 = 0xF1C40F;
+
+/// `(レベル, ターゲット, 本文)`が同じものは同じログとして数える
+type Key = (Level, String, String);
+
+struct Entry {
+    level: Level,
+    target: String,
+    message: String,
+    at: serenity::Timestamp,
+}
+
+impl Entry {
+    fn key(&self) -> Key {
+        (self.level, self.target.clone(), self.message.clone())
+    }
+}
+
+/// `init_tracing_subscriber`で作られ、`spawn_forwarder`で取り出される
+static RECEIVER: LazyLock<Mutex<Option<mpsc::Receiver<Entry>>>> = LazyLock::new(Default::default);
+
+/// `message`を先頭に、ほかのフィールドを`key=value`で続ける
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl MessageVisitor {
+    fn finish(self) -> String {
+        if self.message.is_empty() {
+            self.fields.trim_start().to_owned()
+        } else {
+            self.message + &self.fields
+        }
+    }
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+}
+
+/// スパンのフィールド。どのコマンドで起きたかを本文に含めるために持っておく
+struct SpanFields(String);
+
+pub(super) struct DiscordLayer {
+    sender: mpsc::Sender<Entry>,
+    level: LevelFilter,
+}
+
+impl DiscordLayer {
+    pub(super) fn new(config: &DiscordLog) -> Self {
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        let level = LevelFilter::from_str(&config.level)
+            .unwrap_or(LevelFilter::WARN)
+            .min(LevelFilter::WARN);
+
+        *RECEIVER.lock().expect("failed to lock") = Some(receiver);
+
+        Self { sender, level }
+    }
+
+    /// このレイヤーだけに掛けるフィルター。ほかのレイヤーのDEBUGなどは止めない
+    pub(super) fn filter(&self) -> FilterFn<impl Fn(&Metadata<'_>) -> bool + use<>> {
+        let level = self.level;
+
+        // 細かいスパンまで有効にしないように、スパンはINFO以上だけ見る
+        FilterFn::new(move |metadata| {
+            if metadata.is_span() {
+                *metadata.level() <= Level::INFO
+            } else {
+                level >= *metadata.level()
+            }
+        })
+        .with_max_level_hint(LevelFilter::INFO)
+    }
+}
+
+impl<S> Layer<S> for DiscordLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+
+        attrs.record(&mut visitor);
+
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanFields(visitor.finish()));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+
+        values.record(&mut visitor);
+
+        if let Some(span) = ctx.span(id)
+            && let Some(fields) = span.extensions_mut().get_mut::<SpanFields>()
+        {
+            let _ = write!(fields.0, " {}", visitor.finish());
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+
+        if metadata.is_span() || self.level < *metadata.level() {
+            return;
+        }
+
+        let mut context = Vec::new();
+
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if span.name() == FORWARDER_SPAN {
+                    return;
+                }
+
+                let extensions = span.extensions();
+                let fields = extensions
+                    .get::<SpanFields>()
+                    .map(|f| f.0.as_str())
+                    .unwrap_or_default();
+
+                context.push(format!("{}{{{fields}}}", span.name()));
+            }
+        }
+
+        let mut visitor = MessageVisitor::default();
+
+        event.record(&mut visitor);
+
+        let mut message = visitor.finish();
+
+        if !context.is_empty() {
+            message = format!("{}: {message}", context.join(":"));
+        }
+
+        // いっぱいなら捨てる。ここでログを出すと自分に戻ってくる
+        let _ = self.sender.try_send(Entry {
+            level: *metadata.level(),
+            target: metadata.target().to_owned(),
+            message,
+            at: serenity::Timestamp::now(),
+        });
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_owned()
+    } else {
+        text.chars()
+            .take(max - 1)
+            .chain(std::iter::once('…'))
+            .collect()
+    }
+}
+
+fn embed(entry: &Entry, count: usize) -> serenity::CreateEmbed {
+    let mut embed = serenity::CreateEmbed::new()
+        .title(format!("{} {}", entry.level, entry.target))
+        .description(format!(
+            "```\n{}\n```",
+            truncate(&entry.message, MAX_DESCRIPTION_CHARS)
+        ))
+        .color(if entry.level == Level::ERROR {
+            COLOR_ERROR
+        } else {
+            COLOR_WARN
+        })
+        .timestamp(entry.at);
+
+    if count > 1 {
+        embed = embed.footer(serenity::CreateEmbedFooter::new(t!(
+            CHANNEL_LOCALE,
+            "log.repeated",
+            count = count
+        )));
+    }
+
+    embed
+}
+
+async fn forward(
+    http: Arc<serenity::Http>,
+    config: DiscordLog,
+    mut receiver: mpsc::Receiver<Entry>,
+) {
+    let mut interval = tokio::time::interval(config.interval);
+    // 最後に送った時刻と、それから送らずに数えた回数
+    let mut recent: HashMap<Key, (Instant, usize)> = HashMap::new();
+
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        // 届いた順に、同じログはまとめて数える
+        let mut batch: Vec<(Entry, usize)> = Vec::new();
+        let mut index: HashMap<Key, usize> = HashMap::new();
+
+        while let Ok(entry) = receiver.try_recv() {
+            let key = entry.key();
+
+            match index.get(&key) {
+                Some(&i) => batch[i].1 += 1,
+                None => {
+                    index.insert(key, batch.len());
+                    batch.push((entry, 1));
+                }
+            }
+        }
+
+        if batch.is_empty() {
+            continue;
+        }
+
+        let now = Instant::now();
+
+        recent.retain(|_, (sent, suppressed)| {
+            now.duration_since(*sent) < config.dedup_window || *suppressed > 0
+        });
+
+        let mut pending = Vec::new();
+
+        for (entry, count) in batch {
+            match recent.get_mut(&entry.key()) {
+                Some((sent, suppressed)) if now.duration_since(*sent) < config.dedup_window => {
+                    *suppressed += count;
+                }
+                Some((_, suppressed)) => pending.push((entry, count + std::mem::take(suppressed))),
+                None => pending.push((entry, count)),
+            }
+        }
+
+        if pending.is_empty() {
+            continue;
+        }
+
+        let omitted: usize = pending.iter().skip(MAX_EMBEDS).map(|(_, c)| c).sum();
+
+        pending.truncate(MAX_EMBEDS);
+
+        for (entry, _) in &pending {
+            recent.insert(entry.key(), (now, 0));
+        }
+
+        let embeds: Vec<_> = pending
+            .iter()
+            .map(|(entry, count)| embed(entry, *count))
+            .collect();
+        let mut message = serenity::CreateMessage::new().embeds(embeds);
+
+        if omitted > 0 {
+            message = message.content(t!(CHANNEL_LOCALE, "log.omitted", count = omitted));
+        }
+
+        if let Err(err) = config.channel_id.send_message(&http, message).await {
+            tracing::warn!("failed to forward logs to {}: {err}", config.channel_id);
+        }
+    }
+}
+
+/// ためておいたログの送信を始める。`logging.discord`がなければ何もしない
+pub fn spawn_forwarder(http: Arc<serenity::Http>, config: Option<DiscordLog>) {
+    let Some(config) = config else {
+        return;
+    };
+    let Some(receiver) = RECEIVER.lock().expect("failed to lock").take() else {
+        return;
+    };
+
+    tokio::spawn(forward(http, config, receiver).instrument(tracing::info_span!(FORWARDER_SPAN)));
+}
@@ -0,0 +1,78 @@
+use ::serenity::framework::Framework;
+use poise::serenity_prelude as serenity;
+use tracing::{Instrument, Span};
+
+/// イベントの処理を、コマンド名・ユーザー・サーバーを持つスパンの中で行う
+pub struct Traced<F>(pub F);
+
+/// `dareda start`のようにサブコマンドまで含めた名前
+fn command_name(data: &serenity::CommandData) -> String {
+    let mut name = data.name.clone();
+    let mut options = &data.options;
+
+    while let Some((sub_name, sub_options)) = options.iter().find_map(|o| match &o.value {
+        serenity::CommandDataOptionValue::SubCommand(sub)
+        | serenity::CommandDataOptionValue::SubCommandGroup(sub) => Some((&o.name, sub)),
+        _ => None,
+    }) {
+        name = format!("{name} {sub_name}");
+        options = sub_options;
+    }
+
+    name
+}
+
+fn event_span(event: &serenity::FullEvent) -> Span {
+    let serenity::FullEvent::InteractionCreate { interaction } = event else {
+        return Span::none();
+    };
+    let (span, guild_id) = match interaction {
+        serenity::Interaction::Command(i) | serenity::Interaction::Autocomplete(i) => (
+            tracing::info_span!(
+                "command",
+                command = %command_name(&i.data),
+                user = i.user.id.get(),
+                guild = tracing::field::Empty
+            ),
+            i.guild_id,
+        ),
+        serenity::Interaction::Component(i) => (
+            tracing::info_span!(
+                "interaction",
+                custom_id = %i.data.custom_id,
+                user = i.user.id.get(),
+                guild = tracing::field::Empty
+            ),
+            i.guild_id,
+        ),
+        serenity::Interaction::Modal(i) => (
+            tracing::info_span!(
+                "interaction",
+                custom_id = %i.data.custom_id,
+                user = i.user.id.get(),
+                guild = tracing::field::Empty
+            ),
+            i.guild_id,
+        ),
+        _ => return Span::none(),
+    };
+
+    if let Some(guild_id) = guild_id {
+        span.record("guild", guild_id.get());
+    }
+
+    span
+}
+
+#[serenity::async_trait]
+impl<F: Framework> Framework for Traced<F> {
+    async fn init(&mut self, client: &serenity::Client) {
+        self.0.init(client).await;
+    }
+
+    async fn dispatch(&self, ctx: serenity::Context, event: serenity::FullEvent) {
+        let span = event_span(&event);
+
+        self.0.dispatch(ctx, event).instrument(span).await;
+    }
+}
@@ -0,0 +1,119 @@
+use serde_json::{Map, Value};
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_owned(), Value::from(format!("{value:?}")));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_owned(), Value::from(value));
+    }
+}
+
+fn record(fields: impl RecordFields) -> Map<String, Value> {
+    let mut map = Map::new();
+
+    fields.record(&mut JsonVisitor(&mut map));
+
+    map
+}
+
+/// スパンのフィールドをJSONのオブジェクトとして持つ
+pub struct JsonFields;
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        write!(writer, "{}", Value::Object(record(fields)))
+    }
+
+    fn add_fields(
+        &self,
+        current: &'writer mut FormattedFields<Self>,
+        fields: &tracing::span::Record<'_>,
+    ) -> fmt::Result {
+        let mut map: Map<String, Value> = serde_json::from_str(&current.fields).unwrap_or_default();
+
+        map.extend(record(fields));
+        current.fields = Value::Object(map).to_string();
+
+        Ok(())
+    }
+}
+
+/// 1行に1つのJSONで書き出す
+pub struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut line = Map::new();
+
+        line.insert(
+            "timestamp".to_owned(),
+            Value::from(chrono::Utc::now().to_rfc3339()),
+        );
+        line.insert("level".to_owned(), Value::from(metadata.level().as_str()));
+        line.insert("target".to_owned(), Value::from(metadata.target()));
+        line.insert("fields".to_owned(), Value::Object(record(event)));
+
+        if let Some(scope) = ctx.event_scope() {
+            let spans: Vec<_> = scope
+                .from_root()
+                .map(|span| {
+                    let mut fields: Map<String, Value> = span
+                        .extensions()
+                        .get::<FormattedFields<N>>()
+                        .and_then(|f| serde_json::from_str(&f.fields).ok())
+                        .unwrap_or_default();
+
+                    fields.insert("name".to_owned(), Value::from(span.name()));
+
+                    Value::Object(fields)
+                })
+                .collect();
+
+            line.insert("spans".to_owned(), Value::from(spans));
+        }
+
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
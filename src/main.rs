@@ -1,24 +1,75 @@
 mod bot;
+mod cache;
 mod config;
+mod error;
 mod greeter;
+mod help;
 mod http;
+mod i18n;
 mod image;
 mod logger;
 mod madomagi;
+mod metrics;
+mod monitor;
 mod pokemon;
 mod proxy;
 mod verify;
 
+use bot::component::Router;
 use config::Config;
+use error::Error;
 use poise::serenity_prelude as serenity;
 use std::env;
+use std::sync::Arc;
 
-type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, Data, Error>;
 
 #[derive(Clone)]
 struct Data {
     config: Config,
+    router: Arc<Router>,
+    help: Arc<Vec<help::index::CommandHelp>>,
+}
+
+fn router() -> Router {
+    Router::new()
+        .component(|ctx, data, i, id| Box::pin(verify::handler::handle_component(ctx, data, i, id)))
+        .component(|ctx, data, i, id| Box::pin(proxy::handler::handle_component(ctx, data, i, id)))
+        .component(|ctx, data, i, id| {
+            Box::pin(pokemon::handler::handle_component(ctx, data, i, id))
+        })
+        .component(|ctx, data, i, id| Box::pin(help::handler::handle_component(ctx, data, i, id)))
+        .modal(|ctx, data, i, id| Box::pin(pokemon::handler::handle_modal(ctx, data, i, id)))
+        .autocomplete("help", "command", |ctx, partial| {
+            Box::pin(help::command::autocomplete_command(ctx, partial))
+        })
+        .autocomplete("pokedex", "query", |ctx, partial| {
+            Box::pin(pokemon::command::autocomplete_species(ctx, partial))
+        })
+}
+
+/// 登録するコマンド。英語の説明もここで付ける
+fn commands(captcha_permission: serenity::Permissions) -> Vec<poise::Command<Data, Error>> {
+    let mut commands = vec![
+        bot::command::ping(),
+        bot::command::admin(),
+        help::command::help(),
+        proxy::command::proxy(),
+        proxy::command::proxycheck(),
+        pokemon::command::dareda(),
+        pokemon::command::pokedex(),
+        madomagi::command::dj(),
+        madomagi::command::sayakais(),
+    ];
+    let mut captcha_command = verify::command::captcha();
+
+    captcha_command.default_member_permissions = captcha_permission;
+
+    commands.push(captcha_command);
+
+    i18n::localize_commands(&mut commands);
+
+    commands
 }
 
 #[tokio::main]
@@ -26,10 +77,10 @@ async fn main() -> Result<(), Error> {
     // TODO: .expect()また.ok()にする
     dotenvy::dotenv().unwrap();
 
-    logger::init_tracing_subscriber().expect("setting subscriber failed");
-
     let config = Config::load().map_err(|e| format!("config.toml の読み込みに失敗: {e}"))?;
 
+    logger::init_tracing_subscriber(&config.logging).expect("setting subscriber failed");
+
     // TODO: .expect()にする
     let token = env::var("DISCORD_BOT_TOKEN").unwrap();
 
@@ -39,40 +90,30 @@ async fn main() -> Result<(), Error> {
         | serenity::GatewayIntents::MESSAGE_CONTENT
         | serenity::GatewayIntents::GUILD_MEMBERS;
 
-    let commands = {
-        let mut commands = vec![
-            bot::command::ping(),
-            proxy::command::proxy(),
-            proxy::command::proxycheck(),
-            pokemon::command::dareda(),
-            madomagi::command::dj(),
-            madomagi::command::sayakais(),
-        ];
-
-        let captcha_perm = serenity::Permissions::from_name(
-            &config
-                .verify
-                .captcha_default_permission
-                .trim()
-                .to_ascii_uppercase(),
-        )
-        .unwrap_or(serenity::Permissions::ADMINISTRATOR);
-        let mut captcha_command = verify::command::captcha();
-
-        captcha_command.default_member_permissions = captcha_perm;
-
-        commands.push(captcha_command);
-
-        commands
-    };
+    let captcha_perm = serenity::Permissions::from_name(
+        &config
+            .verify
+            .captcha_default_permission
+            .trim()
+            .to_ascii_uppercase(),
+    )
+    .unwrap_or(serenity::Permissions::ADMINISTRATOR);
+    let commands = commands(captcha_perm);
 
     let options = poise::FrameworkOptions {
         commands,
-        on_error: |err: poise::FrameworkError<'_, Data, Error>| {
+        command_check: Some(|ctx| Box::pin(bot::check::cooldown(ctx))),
+        pre_command: |ctx| {
+            Box::pin(async move {
+                metrics::command_started(ctx.id(), &ctx.command().qualified_name);
+            })
+        },
+        post_command: |ctx| {
             Box::pin(async move {
-                tracing::warn!("Global error: {err}");
+                metrics::command_finished(ctx.id(), &ctx.command().qualified_name);
             })
         },
+        on_error: |err| Box::pin(error::on_error(err)),
 
         event_handler: |ctx, event, _framework: poise::FrameworkContext<'_, Data, _>, data| {
             Box::pin(async move {
@@ -80,19 +121,8 @@ async fn main() -> Result<(), Error> {
                     greeter::handler::handle_member_add(ctx, data, new_member).await?;
                 }
 
-                if let serenity::FullEvent::InteractionCreate { interaction } = event
-                    && let serenity::Interaction::Component(comp) = interaction
-                {
-                    let custom_id = comp.data.custom_id.as_str();
-                    let namespace = custom_id.split(':').next().unwrap_or("");
-
-                    match namespace {
-                        "captcha" => verify::handler::handle_component(ctx, data, comp).await?,
-                        "proxy" => proxy::handler::handle_component(ctx, data, comp).await?,
-                        _ => {
-                            tracing::warn!("unknown component: {}", custom_id);
-                        }
-                    }
+                if let serenity::FullEvent::InteractionCreate { interaction } = event {
+                    data.router.dispatch(ctx, data, interaction).await?;
                 }
                 Ok(())
             })
@@ -115,22 +145,41 @@ async fn main() -> Result<(), Error> {
                         .join(", ")
                 );
 
-                poise::builtins::register_in_guild(
-                    ctx,
-                    &framework.options().commands,
-                    config.guild.guild_id,
-                )
-                .await?;
+                logger::spawn_forwarder(ctx.http.clone(), config.logging.discord.clone());
+
+                bot::register::sync_all(ctx, &config, &framework.options().commands, false).await?;
+
+                tokio::spawn(pokemon::api::load_species_index());
+
+                tokio::spawn(proxy::pool::refresh(config.proxy.clone()));
 
-                Ok(Data { config })
+                if let Some(daily) = config.daily.clone() {
+                    tokio::spawn(pokemon::daily::schedule(ctx.clone(), daily));
+                }
+
+                Ok(Data {
+                    config,
+                    router: Arc::new(router()),
+                    help: Arc::new(help::index::build(&framework.options().commands)),
+                })
             })
         })
         .build();
 
     let mut client = serenity::Client::builder(token, intents)
-        .framework(framework)
+        .framework(logger::Traced(framework))
         .await?;
 
-    client.start().await?;
+    if let Some(monitor) = config.monitor.clone() {
+        tokio::spawn(monitor::serve(monitor, client.shard_manager.clone()));
+    }
+
+    tokio::spawn(bot::shutdown::watch(client.shard_manager.clone()));
+
+    let result = client.start().await;
+
+    bot::shutdown::finish(&config).await;
+
+    result?;
     Ok(())
 }
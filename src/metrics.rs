@@ -0,0 +1,183 @@
+use dashmap::DashMap;
+use std::fmt::Write;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+/// ヒストグラムのバケット（秒）
+const BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// `(名前, 種類, 説明)`。ここにないものは出力しない
+const METRICS: &[(&str, &str, &str)] = &[
+    ("bot_commands_total", "counter", "Slash command invocations"),
+    (
+        "bot_command_duration_seconds",
+        "histogram",
+        "Slash command execution time",
+    ),
+    (
+        "bot_command_errors_total",
+        "counter",
+        "Slash commands that returned an error",
+    ),
+    (
+        "bot_captcha_results_total",
+        "counter",
+        "Answered captcha challenges",
+    ),
+    (
+        "bot_proxy_check_duration_seconds",
+        "histogram",
+        "Time to check a single proxy",
+    ),
+    (
+        "bot_cache_requests_total",
+        "counter",
+        "Cache lookups by result (hit or miss)",
+    ),
+];
+
+type Key = (&'static str, Vec<(&'static str, String)>);
+
+#[derive(Default)]
+struct Histogram {
+    /// `BUCKETS`ごとの累積数
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+static COUNTERS: LazyLock<DashMap<Key, u64>> = LazyLock::new(DashMap::new);
+static HISTOGRAMS: LazyLock<DashMap<Key, Histogram>> = LazyLock::new(DashMap::new);
+
+/// 実行中のコマンドの開始時刻。キーはpoiseの`Context::id`
+static STARTED: LazyLock<DashMap<u64, Instant>> = LazyLock::new(DashMap::new);
+
+fn key(name: &'static str, labels: &[(&'static str, &str)]) -> Key {
+    (
+        name,
+        labels.iter().map(|(k, v)| (*k, (*v).to_owned())).collect(),
+    )
+}
+
+pub fn inc(name: &'static str, labels: &[(&'static str, &str)]) {
+    *COUNTERS.entry(key(name, labels)).or_default() += 1;
+}
+
+pub fn observe(name: &'static str, labels: &[(&'static str, &str)], value: Duration) {
+    let value = value.as_secs_f64();
+    let mut histogram = HISTOGRAMS.entry(key(name, labels)).or_default();
+
+    if histogram.buckets.is_empty() {
+        histogram.buckets = vec![0; BUCKETS.len()];
+    }
+
+    for (count, bound) in histogram.buckets.iter_mut().zip(BUCKETS) {
+        if value <= *bound {
+            *count += 1;
+        }
+    }
+
+    histogram.sum += value;
+    histogram.count += 1;
+}
+
+pub fn command_started(id: u64, command: &str) {
+    STARTED.insert(id, Instant::now());
+    inc("bot_commands_total", &[("command", command)]);
+}
+
+pub fn command_finished(id: u64, command: &str) {
+    if let Some((_, started)) = STARTED.remove(&id) {
+        observe(
+            "bot_command_duration_seconds",
+            &[("command", command)],
+            started.elapsed(),
+        );
+    }
+}
+
+pub fn command_failed(id: u64, command: &str, kind: &str) {
+    command_finished(id, command);
+    inc(
+        "bot_command_errors_total",
+        &[("command", command), ("kind", kind)],
+    );
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_labels(labels: &[(&'static str, String)], extra: Option<(&str, String)>) -> String {
+    let labels: Vec<_> = labels
+        .iter()
+        .map(|(k, v)| (*k, v.clone()))
+        .chain(extra)
+        .map(|(k, v)| format!("{k}=\"{}\"", escape(&v)))
+        .collect();
+
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+/// Prometheusのテキスト形式で書き出す
+pub fn render() -> String {
+    let mut out = String::new();
+
+    for (name, kind, help) in METRICS {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} {kind}");
+
+        let mut counters: Vec<_> = COUNTERS
+            .iter()
+            .filter(|e| e.key().0 == *name)
+            .map(|e| (e.key().1.clone(), *e.value()))
+            .collect();
+
+        counters.sort();
+
+        for (labels, value) in counters {
+            let _ = writeln!(out, "{name}{} {value}", format_labels(&labels, None));
+        }
+
+        let mut histograms: Vec<_> = HISTOGRAMS
+            .iter()
+            .filter(|e| e.key().0 == *name)
+            .map(|e| {
+                let h = e.value();
+
+                (e.key().1.clone(), h.buckets.clone(), h.sum, h.count)
+            })
+            .collect();
+
+        histograms.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (labels, buckets, sum, count) in histograms {
+            for (bound, value) in BUCKETS.iter().zip(buckets) {
+                let _ = writeln!(
+                    out,
+                    "{name}_bucket{} {value}",
+                    format_labels(&labels, Some(("le", bound.to_string())))
+                );
+            }
+
+            let _ = writeln!(
+                out,
+                "{name}_bucket{} {count}",
+                format_labels(&labels, Some(("le", "+Inf".to_owned())))
+            );
+            let _ = writeln!(out, "{name}_sum{} {sum}", format_labels(&labels, None));
+            let _ = writeln!(out, "{name}_count{} {count}", format_labels(&labels, None));
+        }
+    }
+
+    out
+}
@@ -0,0 +1,153 @@
+use crate::{config, metrics};
+use poise::serenity_prelude as serenity;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// リクエストヘッダーとして読む最大サイズ
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+/// ヘッダーを読み終えるまで待つ時間。送ってこない接続をいつまでも抱えないようにする
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn text(status: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into(),
+        }
+    }
+}
+
+/// すべてのシャードがゲートウェイに接続していれば200
+async fn health(shard_manager: &serenity::ShardManager) -> Response {
+    let runners = shard_manager.runners.lock().await;
+    let shards: Vec<_> = runners
+        .iter()
+        .map(|(id, runner)| {
+            serde_json::json!({
+                "id": id.0,
+                "stage": runner.stage.to_string(),
+                "latency_ms": runner.latency.map(|l| l.as_millis() as u64),
+            })
+        })
+        .collect();
+    let connected = !runners.is_empty()
+        && runners
+            .values()
+            .all(|r| r.stage == serenity::ConnectionStage::Connected);
+
+    Response {
+        status: if connected {
+            "200 OK"
+        } else {
+            "503 Service Unavailable"
+        },
+        content_type: "application/json",
+        body: serde_json::json!({
+            "status": if connected { "ok" } else { "unavailable" },
+            "shards": shards,
+        })
+        .to_string(),
+    }
+}
+
+async fn route(request_line: &str, shard_manager: &serenity::ShardManager) -> Response {
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Response::text("400 Bad Request", "bad request\n");
+    };
+    let path = target.split('?').next().unwrap_or(target);
+
+    match (method, path) {
+        ("GET", "/healthz") => health(shard_manager).await,
+        ("GET", "/metrics") => Response {
+            status: "200 OK",
+            content_type: "text/plain; version=0.0.4",
+            body: metrics::render(),
+        },
+        (_, "/healthz" | "/metrics") => {
+            Response::text("405 Method Not Allowed", "method not allowed\n")
+        }
+        _ => Response::text("404 Not Found", "not found\n"),
+    }
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    shard_manager: &serenity::ShardManager,
+) -> Result<(), std::io::Error> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 1024];
+    let read = async {
+        while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < MAX_REQUEST_BYTES {
+            let n = stream.read(&mut chunk).await?;
+
+            if n == 0 {
+                break;
+            }
+
+            buf.extend_from_slice(&chunk[..n]);
+        }
+
+        Ok::<_, std::io::Error>(())
+    };
+
+    tokio::time::timeout(READ_TIMEOUT, read)
+        .await
+        .map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::TimedOut, "request read timed out")
+        })??;
+
+    let request = String::from_utf8_lossy(&buf);
+    let response = route(request.lines().next().unwrap_or(""), shard_manager).await;
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// `/healthz`と`/metrics`を返すHTTPサーバー
+pub async fn serve(config: config::Monitor, shard_manager: Arc<serenity::ShardManager>) {
+    let listener = match TcpListener::bind(config.listen).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            tracing::error!("failed to bind monitor server on {}: {err}", config.listen);
+
+            return;
+        }
+    };
+
+    tracing::info!("monitor server listening on {}", config.listen);
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                tracing::warn!("monitor accept error: {err}");
+
+                continue;
+            }
+        };
+        let shard_manager = Arc::clone(&shard_manager);
+
+        tokio::spawn(async move {
+            if let Err(err) = handle(stream, &shard_manager).await {
+                tracing::debug!("monitor request from {peer} failed: {err}");
+            }
+        });
+    }
+}
@@ -1,2 +1,9 @@
 pub mod api;
 pub mod command;
+mod common;
+pub mod daily;
+mod dex;
+pub mod handler;
+mod question;
+mod quiz;
+pub mod session;
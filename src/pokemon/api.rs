@@ -1,137 +1,457 @@
+use crate::cache::TtlCache;
 use crate::{Error, http};
-use lru::LruCache;
-use pokerust::{Endpoint, FromId};
+use futures::StreamExt;
+use pokerust::{ChainLink, Endpoint, Id, NamedAPIResourceList};
 use rand::Rng;
-use std::collections::HashMap;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
 use std::num::NonZeroUsize;
 use std::sync::{Arc, LazyLock};
-use tokio::sync::{Mutex, OnceCell, RwLock};
+use std::time::Duration;
+use tokio::sync::OnceCell;
 
-static TOTAL_POKEMON: LazyLock<OnceCell<i16>> = LazyLock::new(OnceCell::new);
+const POKEAPI_ENDPOINT: &str = "https://pokeapi.co/api/v2/";
 
-static POKEMON_CACHE: LazyLock<RwLock<HashMap<i16, &'static pokerust::Pokemon>>> =
-    LazyLock::new(|| RwLock::new(HashMap::new()));
+const CACHE_CAPACITY: NonZeroUsize = NonZeroUsize::new(200).unwrap();
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
 
-static POKEMON_SPECIES_CACHE: LazyLock<RwLock<HashMap<i16, &'static pokerust::PokemonSpecies>>> =
-    LazyLock::new(|| RwLock::new(HashMap::new()));
+const IMAGE_CACHE_CAPACITY: NonZeroUsize = NonZeroUsize::new(50).unwrap();
 
-static IMAGE_BYTES_CACHE: LazyLock<Mutex<LruCache<i16, Arc<Vec<u8>>>>> =
-    LazyLock::new(|| Mutex::new(LruCache::new(NonZeroUsize::new(50).unwrap())));
+const SPECIES_INDEX_CONCURRENCY: usize = 8;
+/// 種族1つの取得に失敗したときに待つ時間。使い切ったらその種族は索引に入れない
+const SPECIES_FETCH_RETRY_DELAYS: &[Duration] = &[Duration::from_secs(2), Duration::from_secs(10)];
+/// 索引全体の構築に失敗したときにやり直すまでの時間。失敗が続くほど長くする
+const SPECIES_INDEX_RETRY_DELAYS: &[Duration] = &[
+    Duration::from_secs(60),
+    Duration::from_secs(5 * 60),
+    Duration::from_secs(30 * 60),
+];
 
-pub struct Pokemon {
+static TOTAL_POKEMON: OnceCell<i16> = OnceCell::const_new();
+
+static SPECIES_INDEX: OnceCell<Vec<SpeciesEntry>> = OnceCell::const_new();
+
+static POKEMON_CACHE: LazyLock<TtlCache<i16, pokerust::Pokemon>> =
+    LazyLock::new(|| TtlCache::new("pokemon", CACHE_CAPACITY, CACHE_TTL));
+
+static POKEMON_SPECIES_CACHE: LazyLock<TtlCache<i16, pokerust::PokemonSpecies>> =
+    LazyLock::new(|| TtlCache::new("pokemon_species", CACHE_CAPACITY, CACHE_TTL));
+
+static EVOLUTION_CHAIN_CACHE: LazyLock<TtlCache<i16, pokerust::EvolutionChain>> =
+    LazyLock::new(|| TtlCache::new("evolution_chain", CACHE_CAPACITY, CACHE_TTL));
+
+static ABILITY_CACHE: LazyLock<TtlCache<i16, pokerust::Ability>> =
+    LazyLock::new(|| TtlCache::new("ability", CACHE_CAPACITY, CACHE_TTL));
+
+static TYPE_CACHE: LazyLock<TtlCache<String, pokerust::Type>> =
+    LazyLock::new(|| TtlCache::new("type", CACHE_CAPACITY, CACHE_TTL));
+
+static IMAGE_BYTES_CACHE: LazyLock<TtlCache<i16, Vec<u8>>> =
+    LazyLock::new(|| TtlCache::new("image_bytes", IMAGE_CACHE_CAPACITY, CACHE_TTL));
+
+static CRY_BYTES_CACHE: LazyLock<TtlCache<i16, Vec<u8>>> =
+    LazyLock::new(|| TtlCache::new("cry_bytes", IMAGE_CACHE_CAPACITY, CACHE_TTL));
+
+async fn fetch<T>(path: &str) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    Ok(http::CLIENT
+        .get(format!("{POKEAPI_ENDPOINT}{path}"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+async fn fetch_bytes(url: String) -> Result<Vec<u8>, Error> {
+    Ok(http::CLIENT
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?
+        .to_vec())
+}
+
+async fn fetch_by_id<T>(id: i16) -> Result<T, Error>
+where
+    T: Endpoint + DeserializeOwned,
+{
+    fetch(&format!("{}/{id}/", T::ENDPOINT)).await
+}
+
+/// 検索用に正規化する（小文字化、ひらがなをカタカナに変換）
+pub fn normalize_name(name: &str) -> String {
+    name.trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'ぁ'..='ゖ' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// PokeAPIの言語名は`ja-Hrkt`のように大文字を含むため大小文字を区別しない
+fn is_language(resource: &pokerust::NamedAPIResource<pokerust::Language>, language: &str) -> bool {
+    resource.name.eq_ignore_ascii_case(language)
+}
+
+fn find_name<'a>(names: &'a [pokerust::Name], language: &str) -> Option<&'a str> {
+    names
+        .iter()
+        .find_map(|n| is_language(&n.language, language).then_some(n.name.as_str()))
+}
+
+/// 全言語の名前で検索するためのポケモン種族の索引
+pub struct SpeciesEntry {
     pub id: i16,
+    pub name_ja: Option<String>,
+    pub name_en: Option<String>,
+    normalized_names: Vec<String>,
 }
 
-impl Pokemon {
-    async fn get_pokemon(id: i16) -> Result<&'static pokerust::Pokemon, Error> {
-        {
-            let cache_read = POKEMON_CACHE.read().await;
+impl SpeciesEntry {
+    pub fn label(&self) -> String {
+        match (&self.name_ja, &self.name_en) {
+            (Some(ja), Some(en)) => format!("#{:04} {ja} / {en}", self.id),
+            (Some(name), None) | (None, Some(name)) => format!("#{:04} {name}", self.id),
+            (None, None) => format!("#{:04}", self.id),
+        }
+    }
+
+    fn matches(&self, normalized_query: &str) -> bool {
+        self.normalized_names
+            .iter()
+            .any(|n| n.contains(normalized_query))
+    }
 
-            if let Some(pokemon) = cache_read.get(&id) {
-                return Ok(*pokemon);
+    fn matches_exact(&self, normalized_query: &str) -> bool {
+        self.normalized_names.iter().any(|n| n == normalized_query)
+    }
+}
+
+/// 種族を1つ取得する。一時的な失敗に備えて間を空けてやり直す
+async fn fetch_species_with_retry(id: i16) -> Result<pokerust::PokemonSpecies, Error> {
+    for delay in SPECIES_FETCH_RETRY_DELAYS {
+        match fetch_by_id(id).await {
+            Ok(species) => return Ok(species),
+            Err(err) => {
+                tracing::debug!("fetch pokemon species {id} error, retrying in {delay:?}: {err}");
+                tokio::time::sleep(*delay).await;
             }
         }
+    }
 
-        // TODO: プログラム終了までキャッシュ。メモリリークのおそれ
-        let pokemon = Box::leak(Box::new(pokerust::Pokemon::from_id(id)?));
+    fetch_by_id(id).await
+}
 
-        {
-            let mut cache_write = POKEMON_CACHE.write().await;
+async fn species_entry(id: i16) -> Option<SpeciesEntry> {
+    let species = match fetch_species_with_retry(id).await {
+        Ok(species) => species,
+        Err(err) => {
+            tracing::warn!("skipping pokemon species {id} in index: {err}");
 
-            cache_write.insert(id, pokemon);
+            return None;
         }
+    };
+    let mut normalized_names: Vec<String> = species
+        .names
+        .iter()
+        .map(|n| normalize_name(&n.name))
+        .collect();
+
+    normalized_names.push(normalize_name(&species.name));
+
+    Some(SpeciesEntry {
+        id: species.id,
+        name_ja: find_name(&species.names, "ja-hrkt").map(str::to_owned),
+        name_en: find_name(&species.names, "en").map(str::to_owned),
+        normalized_names,
+    })
+}
+
+/// 種族の索引を構築する。全種族を取得するため起動時にバックグラウンドで実行する。
+/// 一覧の取得に失敗したときは、間を空けて構築できるまでやり直す
+pub async fn load_species_index() {
+    let mut attempt = 0;
+
+    while let Err(err) = try_load_species_index().await {
+        let delay = SPECIES_INDEX_RETRY_DELAYS[attempt.min(SPECIES_INDEX_RETRY_DELAYS.len() - 1)];
+
+        tracing::error!("load pokemon species index error, retrying in {delay:?}: {err}");
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
 
-        Ok(pokemon)
+async fn try_load_species_index() -> Result<(), Error> {
+    SPECIES_INDEX
+        .get_or_try_init(|| async {
+            let list: NamedAPIResourceList<pokerust::PokemonSpecies> = fetch(&format!(
+                "{}/?offset=0&limit=100000",
+                pokerust::PokemonSpecies::ENDPOINT
+            ))
+            .await?;
+
+            // 取れなかった種族は抜けたままにして、ほかの種族で検索できるようにする
+            let mut entries: Vec<SpeciesEntry> = futures::stream::iter(list.results)
+                .map(|r| species_entry(r.id()))
+                .buffer_unordered(SPECIES_INDEX_CONCURRENCY)
+                .filter_map(std::future::ready)
+                .collect()
+                .await;
+
+            entries.sort_by_key(|e| e.id);
+
+            tracing::info!("pokemon species index loaded: {} entries", entries.len());
+
+            Ok::<_, Error>(entries)
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// 名前の一部で種族を検索する。索引の構築が終わっていなければ空
+pub fn search_species(query: &str, limit: usize) -> Vec<&'static SpeciesEntry> {
+    let Some(index) = SPECIES_INDEX.get() else {
+        return Vec::new();
+    };
+    let query = normalize_name(query);
+
+    index
+        .iter()
+        .filter(|e| query.is_empty() || e.matches(&query))
+        .take(limit)
+        .collect()
+}
+
+/// 図鑑番号または名前（任意の言語）から種族を探す
+pub async fn find_species(query: &str) -> Result<Option<Pokemon>, Error> {
+    if let Ok(id) = query.trim().trim_start_matches('#').parse::<i16>() {
+        return Ok((1..=Pokemon::total().await?)
+            .contains(&id)
+            .then_some(Pokemon { id }));
     }
 
-    async fn get_species(id: i16) -> Result<&'static pokerust::PokemonSpecies, Error> {
-        {
-            let cache_read = POKEMON_SPECIES_CACHE.read().await;
+    let query = normalize_name(query);
+
+    if let Some(index) = SPECIES_INDEX.get() {
+        return Ok(index
+            .iter()
+            .find(|e| e.matches_exact(&query))
+            .map(|e| Pokemon { id: e.id }));
+    }
 
-            if let Some(species) = cache_read.get(&id) {
-                return Ok(*species);
+    // 索引の構築中は英語名で直接問い合わせる
+    if !query.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Ok(None);
+    }
+
+    let response = http::CLIENT
+        .get(format!(
+            "{POKEAPI_ENDPOINT}{}/{query}/",
+            pokerust::PokemonSpecies::ENDPOINT
+        ))
+        .send()
+        .await?;
+
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    let species: pokerust::PokemonSpecies = response.error_for_status()?.json().await?;
+
+    Ok(Some(Pokemon { id: species.id }))
+}
+
+pub async fn ability_name(id: i16, language: &str) -> Result<Option<String>, Error> {
+    let ability = ABILITY_CACHE
+        .get_or_try_insert_with(id, || fetch_by_id(id))
+        .await?;
+
+    Ok(find_name(&ability.names, language).map(str::to_owned))
+}
+
+/// `attack`タイプのわざを`defense`タイプに当てたときのダメージ倍率
+pub async fn damage_multiplier(attack: &str, defense: &[String]) -> Result<f32, Error> {
+    let path = format!("{}/{attack}/", pokerust::Type::ENDPOINT);
+    let relations = TYPE_CACHE
+        .get_or_try_insert_with(attack.to_owned(), || fetch(&path))
+        .await?;
+    let relations = &relations.damage_relations;
+
+    Ok(defense
+        .iter()
+        .map(|d| {
+            let has = |list: &[pokerust::NamedAPIResource<pokerust::Type>]| {
+                list.iter().any(|t| &t.name == d)
+            };
+
+            if has(&relations.no_damage_to) {
+                0.0
+            } else if has(&relations.double_damage_to) {
+                2.0
+            } else if has(&relations.half_damage_to) {
+                0.5
+            } else {
+                1.0
             }
-        }
+        })
+        .product())
+}
+
+pub struct Pokemon {
+    pub id: i16,
+}
 
-        // TODO: プログラム終了までキャッシュ。メモリリークのおそれ
-        let species = Box::leak(Box::new(pokerust::PokemonSpecies::from_id(id)?));
+impl Pokemon {
+    async fn get_pokemon(id: i16) -> Result<Arc<pokerust::Pokemon>, Error> {
+        POKEMON_CACHE
+            .get_or_try_insert_with(id, || fetch_by_id(id))
+            .await
+    }
+
+    async fn get_species(id: i16) -> Result<Arc<pokerust::PokemonSpecies>, Error> {
+        POKEMON_SPECIES_CACHE
+            .get_or_try_insert_with(id, || fetch_by_id(id))
+            .await
+    }
 
-        {
-            let mut cache_write = POKEMON_SPECIES_CACHE.write().await;
+    pub async fn species(&self) -> Result<Arc<pokerust::PokemonSpecies>, Error> {
+        Self::get_species(self.id).await
+    }
+
+    /// `index`番目のフォルム。範囲外なら`None`
+    pub async fn variety(&self, index: usize) -> Result<Option<Arc<pokerust::Pokemon>>, Error> {
+        let species = Self::get_species(self.id).await?;
+        let Some(variety) = species.varieties.get(index) else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self::get_pokemon(variety.pokemon.id()).await?))
+    }
 
-            cache_write.insert(id, species);
+    /// 進化の段階ごとの種族ID
+    pub async fn evolution_stages(&self) -> Result<Vec<Vec<i16>>, Error> {
+        fn collect(link: &ChainLink, depth: usize, stages: &mut Vec<Vec<i16>>) {
+            if stages.len() <= depth {
+                stages.push(Vec::new());
+            }
+
+            stages[depth].push(link.species.id());
+
+            for next in &link.evolves_to {
+                collect(next, depth + 1, stages);
+            }
         }
 
-        Ok(species)
+        let chain_id = Self::get_species(self.id).await?.evolution_chain.id();
+        let chain = EVOLUTION_CHAIN_CACHE
+            .get_or_try_insert_with(chain_id, || fetch_by_id(chain_id))
+            .await?;
+        let mut stages = Vec::new();
+
+        collect(&chain.chain, 0, &mut stages);
+
+        Ok(stages)
     }
 
-    pub async fn name(&self) -> Result<Option<&'static str>, Error> {
+    pub async fn name_in(&self, language: &str) -> Result<Option<String>, Error> {
+        Ok(find_name(&Self::get_species(self.id).await?.names, language).map(str::to_owned))
+    }
+
+    pub async fn name(&self) -> Result<Option<String>, Error> {
+        self.name_in("ja-hrkt").await
+    }
+
+    /// 最新の図鑑説明文
+    pub async fn latest_flavor_text_in(&self, language: &str) -> Result<Option<String>, Error> {
         Ok(Self::get_species(self.id)
             .await?
-            .names
+            .flavor_text_entries
             .iter()
-            .find_map(|n| (n.language.name == "ja-hrkt").then_some(n.name.as_str())))
+            .rev()
+            .find_map(|f| is_language(&f.language, language).then(|| f.flavor_text.clone())))
     }
 
-    pub async fn flavor_text(&self) -> Result<Option<&'static str>, Error> {
+    pub async fn flavor_text(&self) -> Result<Option<String>, Error> {
         Ok(Self::get_species(self.id)
             .await?
             .flavor_text_entries
             .iter()
-            .find_map(|f| (f.language.name == "ja-hrkt").then_some(f.flavor_text.as_str())))
+            .find_map(|f| is_language(&f.language, "ja-hrkt").then(|| f.flavor_text.clone())))
     }
 
-    pub async fn image_url(&self) -> Result<Option<&'static str>, Error> {
+    pub async fn image_url(&self) -> Result<Option<String>, Error> {
         let pokemon = Self::get_pokemon(self.id).await?;
 
-        Ok(pokemon.sprites.front_default.as_deref())
+        Ok(pokemon.sprites.front_default.clone())
     }
 
     pub async fn image_bytes(&self) -> Result<Option<Arc<Vec<u8>>>, Error> {
-        let mut cache = IMAGE_BYTES_CACHE.lock().await;
-
-        if let Some(bytes) = cache.get(&self.id) {
-            return Ok(Some(Arc::clone(bytes)));
-        }
-
         let Some(image_url) = self.image_url().await? else {
             return Ok(None);
         };
 
-        let bytes = Arc::new(
-            http::CLIENT
-                .get(image_url)
-                .send()
-                .await?
-                .bytes()
-                .await?
-                .to_vec(),
-        );
-
-        cache.put(self.id, bytes.clone());
+        let bytes = IMAGE_BYTES_CACHE
+            .get_or_try_insert_with(self.id, || fetch_bytes(image_url))
+            .await?;
 
         Ok(Some(bytes))
     }
 
-    pub fn total() -> Result<i16, Error> {
-        if let Some(&count) = TOTAL_POKEMON.get() {
-            return Ok(count);
-        }
+    /// 鳴き声（ogg）
+    pub async fn cry_bytes(&self) -> Result<Option<Arc<Vec<u8>>>, Error> {
+        let pokemon = Self::get_pokemon(self.id).await?;
+        let Some(cry_url) = pokemon
+            .cries
+            .latest
+            .clone()
+            .or(pokemon.cries.legacy.clone())
+        else {
+            return Ok(None);
+        };
 
-        let list = pokerust::PokemonSpecies::list(0, 1)?;
-        let count = list.count as i16;
+        let bytes = CRY_BYTES_CACHE
+            .get_or_try_insert_with(self.id, || fetch_bytes(cry_url))
+            .await?;
 
-        TOTAL_POKEMON.set(count)?;
+        Ok(Some(bytes))
+    }
+
+    /// タイプ名（英語のスラッグ）
+    pub async fn types(&self) -> Result<Vec<String>, Error> {
+        Ok(Self::get_pokemon(self.id)
+            .await?
+            .types
+            .iter()
+            .map(|t| t.type_.name.clone())
+            .collect())
+    }
 
-        Ok(count)
+    pub async fn total() -> Result<i16, Error> {
+        TOTAL_POKEMON
+            .get_or_try_init(|| async {
+                let list: NamedAPIResourceList<pokerust::PokemonSpecies> = fetch(&format!(
+                    "{}/?offset=0&limit=1",
+                    pokerust::PokemonSpecies::ENDPOINT
+                ))
+                .await?;
+
+                Ok(list.count as i16)
+            })
+            .await
+            .copied()
     }
 
-    pub fn random<R>(rng: &mut R) -> Result<Self, Error>
-    where
-        R: Rng,
-    {
-        let total = Self::total()?;
-        let id = rng.gen_range(0..total);
+    pub async fn random() -> Result<Self, Error> {
+        let total = Self::total().await?;
+        let id = rand::thread_rng().gen_range(1..=total);
 
         Ok(Self { id })
     }
@@ -1,189 +1,128 @@
 use crate::{
     Context, Error,
-    image::{alpha_to_mask, background, encode_webp},
-    pokemon::api::Pokemon,
+    bot::check::is_staff,
+    bot::component::route_autocomplete,
+    i18n::{Locale, t},
+    pokemon::api,
+    pokemon::dex::{self, DexState},
+    pokemon::question::QuizKind,
+    pokemon::quiz::{self, QuizMode},
+    pokemon::session,
 };
-use futures::StreamExt;
-use image::ImageReader;
 use poise::serenity_prelude as serenity;
-use rand::thread_rng;
-use std::io::Cursor;
-use wana_kana::ConvertJapanese;
-
-/// ポケモンのシルエットクイズができます。
-#[poise::command(slash_command, guild_only)] // future cannot be sent between threads safely
-pub async fn dareda(ctx: Context<'_>) -> Result<(), Error> {
-    let pokemon = Pokemon::random(&mut thread_rng());
-    let pokemon = match pokemon {
-        Ok(pokemon) => pokemon,
-        Err(err) => {
-            tracing::error!("fetch pokemon error: {err}");
-            ctx.reply("ポケモンが見つかりませんでした").await?;
-
-            return Ok(());
-        }
-    };
 
-    let Some(pokemon_image) = pokemon
-        .image_bytes()
-        .await
-        .inspect_err(|err| {
-            tracing::error!("fetch pokemon image error: {err}");
-        })
-        .ok()
-        .flatten()
-        .and_then(|bytes| {
-            ImageReader::new(Cursor::new(bytes.as_ref()))
-                .with_guessed_format()
-                .ok()?
-                .decode()
-                .inspect_err(|err| {
-                    tracing::error!("decode pokemon image error: {err}");
-                })
-                .ok()
-        })
-    else {
-        ctx.reply("ポケモンの画像が取得できませんでした").await?;
+/// ポケモンのクイズができます。
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("dareda_start", "dareda_stop"),
+    subcommand_required
+)]
+pub async fn dareda(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// 進行中のクイズを中断します（出題者またはスタッフのみ）
+#[poise::command(slash_command, guild_only, rename = "stop")]
+pub async fn dareda_stop(ctx: Context<'_>) -> Result<(), Error> {
+    let locale = Locale::of(ctx);
+    let Some(owner) = session::owner(ctx.channel_id()) else {
+        ctx.send(
+            poise::CreateReply::default()
+                .content(t!(locale, "pokemon.quiz.no_session"))
+                .ephemeral(true),
+        )
+        .await?;
 
         return Ok(());
     };
-    let Some(name) = pokemon.name().await? else {
-        ctx.reply("ポケモンの名前が取得できませんでした").await?;
+
+    if owner != ctx.author().id && !is_staff(ctx).await? {
+        ctx.send(
+            poise::CreateReply::default()
+                .content(t!(locale, "common.no_permission"))
+                .ephemeral(true),
+        )
+        .await?;
 
         return Ok(());
-    };
-    let normalized_name = name.to_katakana();
-    let flavor_text = pokemon
-        .flavor_text()
-        .await?
-        .map(|f| format!("\n説明：{}", f.replace('\n', "　")))
-        .unwrap_or("".to_owned());
-    let correct = format!(
-        "{name}でした！\n\n全国図鑑番号：{id}{flavor_text}",
-        id = pokemon.id
-    );
-    let result_image = background(&pokemon_image);
-    // TODO: ファイル名
-    let attachment = serenity::CreateAttachment::bytes(encode_webp(&result_image)?, "pokemon.webp");
-    let silhouette_image = alpha_to_mask(&pokemon_image);
-    let data = ctx.data();
-    let reply = ctx.send(
+    }
+
+    session::stop(ctx.channel_id());
+
+    ctx.send(
         poise::CreateReply::default()
-            .content(
-                "だーれだ？\n".to_owned()
-                    + "返信で答えてみよう（ひらがな/カタカナ/ローマ字）\n"
-                    + &format!("制限時間は{}分、{}回まで回答できるよ\n", data.config.pokemon.time_limit.as_secs() / 60, data.config.pokemon.max_retry)
-                    + "どうしてもわかんないよ！ってときは「ギブアップ」って返信してね（コマンド実行者のみ）"
-        )
-            .attachment(serenity::CreateAttachment::bytes(
-                encode_webp(&silhouette_image)?,
-                "pokemon.webp",
-            )),
+            .content(t!(locale, "pokemon.quiz.stopped"))
+            .ephemeral(true),
     )
     .await?;
-    let reply_message = reply.message().await?;
-    let reply_message_id = reply_message.id;
-
-    let mut collector = ctx
-        .channel_id()
-        .await_reply(ctx)
-        .filter(move |m| {
-            m.message_reference
-                .as_ref()
-                .and_then(|r| r.message_id.as_ref())
-                == Some(&reply_message_id)
-        })
-        .timeout(data.config.pokemon.time_limit)
-        .stream();
-    let mut retry = 0;
-
-    while let Some(m) = collector.next().await {
-        let answer = m.content.trim().to_katakana();
-
-        if answer == normalized_name {
-            ctx.channel_id()
-                .send_message(
-                    ctx,
-                    serenity::CreateMessage::new()
-                        .add_file(attachment)
-                        .reference_message(&m)
-                        .content(format!("あたり！\n{correct}"))
-                        .allowed_mentions(
-                            serenity::CreateAllowedMentions::new()
-                                .replied_user(false)
-                                .everyone(false)
-                                .all_users(false)
-                                .all_roles(false),
-                        ),
-                )
-                .await?;
-
-            return Ok(());
-        }
-
-        if answer == "ギブアップ" && m.author.id == ctx.author().id {
-            ctx.channel_id()
-                .send_message(
-                    ctx,
-                    serenity::CreateMessage::new()
-                        .add_file(attachment)
-                        .reference_message(&m)
-                        .content(format!("ざんねん！\n{correct}"))
-                        .allowed_mentions(
-                            serenity::CreateAllowedMentions::new()
-                                .replied_user(false)
-                                .everyone(false)
-                                .all_users(false)
-                                .all_roles(false),
-                        ),
-                )
-                .await?;
-
-            return Ok(());
-        }
-
-        m.reply(ctx, "はずれ！").await?;
-
-        retry += 1;
-
-        if retry > data.config.pokemon.max_retry {
-            ctx.channel_id()
-                .send_message(
-                    ctx,
-                    serenity::CreateMessage::new()
-                        .add_file(attachment)
-                        .reference_message(&m)
-                        .content(format!("解答可能回数がなくなりました\n{correct}"))
-                        .allowed_mentions(
-                            serenity::CreateAllowedMentions::new()
-                                .replied_user(false)
-                                .everyone(false)
-                                .all_users(false)
-                                .all_roles(false),
-                        ),
-                )
-                .await?;
-
-            return Ok(());
-        }
-    }
 
-    ctx.channel_id()
-        .send_message(
-            ctx,
-            serenity::CreateMessage::new()
-                .add_file(attachment)
-                .reference_message((ctx.channel_id(), reply_message_id))
-                .content(format!("時間切れ！\n{correct}"))
-                .allowed_mentions(
-                    serenity::CreateAllowedMentions::new()
-                        .replied_user(false)
-                        .everyone(false)
-                        .all_users(false)
-                        .all_roles(false),
-                ),
+    Ok(())
+}
+
+/// ポケモンのクイズができます。
+#[poise::command(slash_command, guild_only, rename = "start")]
+pub async fn dareda_start(
+    ctx: Context<'_>,
+    #[description = "クイズの種類（デフォルトはシルエット）"] kind: Option<QuizKind>,
+    #[description = "回答方法（デフォルトは自由回答）"] mode: Option<QuizMode>,
+) -> Result<(), Error> {
+    let locale = Locale::of(ctx);
+    let kind = kind.unwrap_or(QuizKind::Silhouette);
+    let mode = mode.unwrap_or(QuizMode::Free);
+    let Some((session, answers)) = session::start(ctx.channel_id(), ctx.author().id) else {
+        ctx.send(
+            poise::CreateReply::default()
+                .content(t!(locale, "pokemon.quiz.already_running"))
+                .ephemeral(true),
         )
         .await?;
 
+        return Ok(());
+    };
+
+    ctx.defer().await?;
+
+    let question = kind.question(mode, locale).await?;
+
+    quiz::run(ctx, session, answers, question).await
+}
+
+pub async fn autocomplete_species(
+    _ctx: Context<'_>,
+    partial: &str,
+) -> Vec<serenity::AutocompleteChoice> {
+    api::search_species(partial, 25)
+        .into_iter()
+        .map(|e| serenity::AutocompleteChoice::new(e.label(), e.id.to_string()))
+        .collect()
+}
+
+/// ポケモン図鑑を表示します
+#[poise::command(slash_command, guild_only)]
+pub async fn pokedex(
+    ctx: Context<'_>,
+    #[description = "ポケモンの名前（どの言語でも可）または全国図鑑番号"]
+    #[autocomplete = "route_autocomplete"]
+    query: String,
+) -> Result<(), Error> {
+    let locale = Locale::of(ctx);
+
+    ctx.defer().await?;
+
+    let Some(pokemon) = api::find_species(&query).await? else {
+        return Err(Error::user(t!(locale, "pokemon.not_found")));
+    };
+    let Some((embed, row)) = dex::build(DexState::new(pokemon.id), locale).await? else {
+        return Err(Error::user(t!(locale, "pokemon.not_found")));
+    };
+
+    ctx.send(
+        poise::CreateReply::default()
+            .embed(embed)
+            .components(vec![row]),
+    )
+    .await?;
+
     Ok(())
 }
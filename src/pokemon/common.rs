@@ -0,0 +1,79 @@
+use crate::bot::component::ComponentId;
+use crate::pokemon::dex::DexState;
+
+// color
+pub const COLOR_DEX: u32 = 0xE3350D;
+
+pub const ANSWER_INPUT_ID: &str = "answer";
+
+pub enum PokemonComponent {
+    Dex(DexState),
+    /// クイズのセッションIDと回答モーダルを開くボタン
+    QuizAnswer(u64),
+    QuizChoice(u64, usize),
+    QuizModal(u64),
+    /// 今日のポケモンの日付
+    DailyAnswer(i64),
+    DailyModal(i64),
+}
+
+impl ComponentId for PokemonComponent {
+    const NAMESPACE: &'static str = "pokemon";
+
+    fn fields(&self) -> Vec<String> {
+        match self {
+            PokemonComponent::Dex(state) => vec![
+                "dex".to_owned(),
+                state.species_id.to_string(),
+                state.variety.to_string(),
+                u8::from(state.shiny).to_string(),
+            ],
+            PokemonComponent::QuizAnswer(session_id) => {
+                vec![
+                    "quiz".to_owned(),
+                    session_id.to_string(),
+                    "answer".to_owned(),
+                ]
+            }
+            PokemonComponent::QuizChoice(session_id, index) => vec![
+                "quiz".to_owned(),
+                session_id.to_string(),
+                "choice".to_owned(),
+                index.to_string(),
+            ],
+            PokemonComponent::QuizModal(session_id) => {
+                vec![
+                    "quiz".to_owned(),
+                    session_id.to_string(),
+                    "modal".to_owned(),
+                ]
+            }
+            PokemonComponent::DailyAnswer(day) => {
+                vec!["daily".to_owned(), day.to_string(), "answer".to_owned()]
+            }
+            PokemonComponent::DailyModal(day) => {
+                vec!["daily".to_owned(), day.to_string(), "modal".to_owned()]
+            }
+        }
+    }
+
+    fn parse(fields: &[&str]) -> Option<Self> {
+        Some(match fields {
+            ["dex", species_id, variety, shiny] => PokemonComponent::Dex(DexState {
+                species_id: species_id.parse().ok()?,
+                variety: variety.parse().ok()?,
+                shiny: *shiny == "1",
+            }),
+            ["quiz", session_id, "answer"] => {
+                PokemonComponent::QuizAnswer(session_id.parse().ok()?)
+            }
+            ["quiz", session_id, "choice", index] => {
+                PokemonComponent::QuizChoice(session_id.parse().ok()?, index.parse().ok()?)
+            }
+            ["quiz", session_id, "modal"] => PokemonComponent::QuizModal(session_id.parse().ok()?),
+            ["daily", day, "answer"] => PokemonComponent::DailyAnswer(day.parse().ok()?),
+            ["daily", day, "modal"] => PokemonComponent::DailyModal(day.parse().ok()?),
+            _ => return None,
+        })
+    }
+}
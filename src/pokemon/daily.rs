@@ -0,0 +1,444 @@
+use crate::bot::component::ComponentId;
+use crate::config::Daily;
+use crate::i18n::{Locale, t};
+use crate::image::{alpha_to_mask, encode_webp};
+use crate::pokemon::api::Pokemon;
+use crate::pokemon::common::{ANSWER_INPUT_ID, PokemonComponent};
+use crate::pokemon::question::{image, name, reveal, reveal_attachment};
+use crate::pokemon::quiz::normalize_answer;
+use crate::{Data, Error};
+use poise::serenity_prelude::{self as serenity, Mentionable};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, OnceCell};
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+/// チャンネルに投稿する出題・発表のロケール
+const CHANNEL_LOCALE: Locale = Locale::Ja;
+/// 出題や発表に失敗したときの再試行までの待ち時間
+const RETRY_DELAYS: &[Duration] = &[
+    Duration::from_secs(30),
+    Duration::from_secs(2 * 60),
+    Duration::from_secs(10 * 60),
+];
+
+#[derive(Default, Serialize, Deserialize)]
+struct Streak {
+    last_day: i64,
+    count: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Challenge {
+    day: i64,
+    pokemon_id: i16,
+    message_id: serenity::MessageId,
+    /// 正解した順
+    solvers: Vec<serenity::UserId>,
+    attempts: HashMap<serenity::UserId, usize>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct DailyState {
+    current: Option<Challenge>,
+    streaks: HashMap<serenity::UserId, Streak>,
+}
+
+/// 発表に使う前日の問題。ロックを放してから発表できるように写しておく
+struct Previous {
+    day: i64,
+    pokemon_id: i16,
+    message_id: serenity::MessageId,
+    /// 正解した順の`(ユーザー, 連続正解日数)`
+    solvers: Vec<(serenity::UserId, u32)>,
+}
+
+impl Previous {
+    fn of(state: &DailyState, challenge: &Challenge) -> Self {
+        Self {
+            day: challenge.day,
+            pokemon_id: challenge.pokemon_id,
+            message_id: challenge.message_id,
+            solvers: challenge
+                .solvers
+                .iter()
+                .map(|user_id| (*user_id, state.streaks.get(user_id).map_or(1, |s| s.count)))
+                .collect(),
+        }
+    }
+}
+
+static STATE: OnceCell<Mutex<DailyState>> = OnceCell::const_new();
+
+async fn load_state(config: &Daily) -> Result<&'static Mutex<DailyState>, Error> {
+    STATE
+        .get_or_try_init(|| async {
+            let state = match tokio::fs::read(&config.state_path).await {
+                Ok(bytes) => serde_json::from_slice(&bytes)?,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => DailyState::default(),
+                Err(err) => return Err(err.into()),
+            };
+
+            Ok::<_, Error>(Mutex::new(state))
+        })
+        .await
+}
+
+/// 書き込み中に止まっても壊れないように、一時ファイルに書いてから置き換える
+async fn save(config: &Daily, state: &DailyState) -> Result<(), Error> {
+    let tmp_path = format!("{}.tmp", config.state_path);
+
+    tokio::fs::write(&tmp_path, serde_json::to_vec_pretty(state)?).await?;
+    tokio::fs::rename(&tmp_path, &config.state_path).await?;
+
+    Ok(())
+}
+
+/// 終了前に状態を書き出す。処理中の回答や出題があれば終わるまで待つ
+pub async fn persist(config: &Daily) -> Result<(), Error> {
+    let Some(state) = STATE.get() else {
+        return Ok(());
+    };
+    let state = state.lock().await;
+
+    save(config, &state).await
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+fn parse_post_time(post_time: &str) -> Option<i64> {
+    let (h, m) = post_time.trim().split_once(':')?;
+    let (h, m): (i64, i64) = (h.parse().ok()?, m.parse().ok()?);
+
+    ((0..24).contains(&h) && (0..60).contains(&m)).then_some(h * 60 * 60 + m * 60)
+}
+
+/// 出題時刻を区切りとした日付。出題時刻から次の出題時刻までが同じ日になる
+fn challenge_day(config: &Daily, post_secs: i64, at: i64) -> i64 {
+    (at + config.utc_offset_hours * 60 * 60 - post_secs).div_euclid(DAY_SECS)
+}
+
+fn until_next_post(config: &Daily, post_secs: i64) -> Duration {
+    let local = now() + config.utc_offset_hours * 60 * 60 - post_secs;
+
+    Duration::from_secs((DAY_SECS - local.rem_euclid(DAY_SECS)) as u64)
+}
+
+/// 設定された時刻に毎日出題する
+pub async fn schedule(ctx: serenity::Context, config: Daily) {
+    let Some(post_secs) = parse_post_time(&config.post_time) else {
+        tracing::error!("invalid daily post_time: {}", config.post_time);
+
+        return;
+    };
+
+    // 停止中に出題時刻を過ぎていたらすぐに交代する
+    let overdue = match load_state(&config).await {
+        Ok(state) => state
+            .lock()
+            .await
+            .current
+            .as_ref()
+            .is_none_or(|c| c.day < challenge_day(&config, post_secs, now())),
+        Err(err) => {
+            tracing::error!("load daily state error: {err}");
+
+            return;
+        }
+    };
+
+    if overdue {
+        rotate_with_retry(&ctx, &config, post_secs).await;
+    }
+
+    loop {
+        tokio::time::sleep(until_next_post(&config, post_secs)).await;
+
+        rotate_with_retry(&ctx, &config, post_secs).await;
+    }
+}
+
+/// 失敗したらしばらく待ってやり直す。最後まで発表できなくても新しい問題は出す
+async fn rotate_with_retry(ctx: &serenity::Context, config: &Daily, post_secs: i64) {
+    for delay in RETRY_DELAYS {
+        match rotate(ctx, config, post_secs, true).await {
+            Ok(()) => return,
+            Err(err) => {
+                tracing::warn!("daily challenge error, retrying in {delay:?}: {err}");
+                tokio::time::sleep(*delay).await;
+            }
+        }
+    }
+
+    if let Err(err) = rotate(ctx, config, post_secs, false).await {
+        tracing::error!("daily challenge error: {err}");
+    }
+}
+
+/// 前日の答えを発表して新しい問題を出す。`announce_previous`が`false`なら発表せずに捨てる。
+/// 回答の受け付けを待たせないように、通信や画像の変換はロックを持たずに行う
+async fn rotate(
+    ctx: &serenity::Context,
+    config: &Daily,
+    post_secs: i64,
+    announce_previous: bool,
+) -> Result<(), Error> {
+    let lock = load_state(config).await?;
+    let pokemon = Pokemon::random().await?;
+    let silhouette = serenity::CreateAttachment::bytes(
+        encode_webp(&alpha_to_mask(&image(&pokemon).await?))?,
+        "pokemon.webp",
+    );
+    let previous = {
+        let state = lock.lock().await;
+
+        state.current.as_ref().map(|c| Previous::of(&state, c))
+    };
+
+    if let Some(previous) = previous {
+        if announce_previous {
+            // 発表できるまでは解答者を残しておく
+            announce(ctx, config, &previous).await?;
+        } else {
+            tracing::error!(
+                "giving up announcing daily challenge of day {}",
+                previous.day
+            );
+        }
+
+        let mut state = lock.lock().await;
+
+        if state
+            .current
+            .as_ref()
+            .is_some_and(|c| c.day == previous.day)
+        {
+            state.current = None;
+            save(config, &state).await?;
+        }
+    }
+
+    let day = challenge_day(config, post_secs, now());
+    let button = serenity::CreateButton::new(PokemonComponent::DailyAnswer(day).encode()?)
+        .label(t!(CHANNEL_LOCALE, "pokemon.answer_button"))
+        .style(serenity::ButtonStyle::Primary);
+    let message = config
+        .channel_id
+        .send_message(
+            ctx,
+            serenity::CreateMessage::new()
+                .content(t!(CHANNEL_LOCALE, "pokemon.daily.prompt"))
+                .add_file(silhouette)
+                .components(vec![serenity::CreateActionRow::Buttons(vec![button])]),
+        )
+        .await?;
+
+    let mut state = lock.lock().await;
+
+    state.current = Some(Challenge {
+        day,
+        pokemon_id: pokemon.id,
+        message_id: message.id,
+        solvers: Vec::new(),
+        attempts: HashMap::new(),
+    });
+
+    save(config, &state).await
+}
+
+async fn announce(
+    ctx: &serenity::Context,
+    config: &Daily,
+    challenge: &Previous,
+) -> Result<(), Error> {
+    let pokemon = Pokemon {
+        id: challenge.pokemon_id,
+    };
+    let name = name(&pokemon).await?;
+    let solvers = if challenge.solvers.is_empty() {
+        t!(CHANNEL_LOCALE, "pokemon.daily.no_solvers")
+    } else {
+        challenge
+            .solvers
+            .iter()
+            .enumerate()
+            .map(|(i, (user_id, streak))| {
+                t!(
+                    CHANNEL_LOCALE,
+                    "pokemon.daily.solver",
+                    rank = i + 1,
+                    user = user_id.mention(),
+                    streak = streak
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let _ = config
+        .channel_id
+        .edit_message(
+            ctx,
+            challenge.message_id,
+            serenity::EditMessage::new().components(Vec::new()),
+        )
+        .await;
+
+    config
+        .channel_id
+        .send_message(
+            ctx,
+            serenity::CreateMessage::new()
+                .reference_message((config.channel_id, challenge.message_id))
+                .content(t!(
+                    CHANNEL_LOCALE,
+                    "pokemon.daily.announce",
+                    reveal = reveal(&pokemon, &name, CHANNEL_LOCALE).await?,
+                    solvers = solvers
+                ))
+                .add_file(reveal_attachment(&image(&pokemon).await?)?)
+                .allowed_mentions(
+                    serenity::CreateAllowedMentions::new()
+                        .replied_user(false)
+                        .everyone(false)
+                        .all_users(false)
+                        .all_roles(false),
+                ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn respond_ephemeral(content: impl Into<String>) -> serenity::CreateInteractionResponse {
+    serenity::CreateInteractionResponse::Message(
+        serenity::CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true),
+    )
+}
+
+pub async fn handle_component(
+    ctx: &serenity::Context,
+    i: &serenity::ComponentInteraction,
+    day: i64,
+) -> Result<(), Error> {
+    let locale = Locale::from_discord(&i.locale);
+    let input = serenity::CreateInputText::new(
+        serenity::InputTextStyle::Short,
+        t!(locale, "pokemon.answer_label"),
+        ANSWER_INPUT_ID,
+    )
+    .required(true);
+
+    i.create_response(
+        ctx,
+        serenity::CreateInteractionResponse::Modal(
+            serenity::CreateModal::new(
+                PokemonComponent::DailyModal(day).encode()?,
+                t!(locale, "pokemon.daily.modal_title"),
+            )
+            .components(vec![serenity::CreateActionRow::InputText(input)]),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn handle_modal(
+    ctx: &serenity::Context,
+    data: &Data,
+    i: &serenity::ModalInteraction,
+    day: i64,
+    answer: &str,
+) -> Result<(), Error> {
+    let locale = Locale::from_discord(&i.locale);
+    let Some(config) = &data.config.daily else {
+        i.create_response(ctx, respond_ephemeral(t!(locale, "pokemon.daily.closed")))
+            .await?;
+
+        return Ok(());
+    };
+    let user_id = i.user.id;
+    let mut state = load_state(config).await?.lock().await;
+    let Some(challenge) = state.current.as_mut().filter(|c| c.day == day) else {
+        i.create_response(ctx, respond_ephemeral(t!(locale, "pokemon.daily.closed")))
+            .await?;
+
+        return Ok(());
+    };
+
+    if challenge.solvers.contains(&user_id) {
+        i.create_response(
+            ctx,
+            respond_ephemeral(t!(locale, "pokemon.daily.already_solved")),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    let attempts = challenge.attempts.entry(user_id).or_default();
+
+    if *attempts > data.config.pokemon.max_retry {
+        i.create_response(ctx, respond_ephemeral(t!(locale, "pokemon.out_of_tries")))
+            .await?;
+
+        return Ok(());
+    }
+
+    *attempts += 1;
+
+    let remaining = (data.config.pokemon.max_retry + 1).saturating_sub(*attempts);
+    let name = name(&Pokemon {
+        id: challenge.pokemon_id,
+    })
+    .await?;
+
+    if normalize_answer(answer) != normalize_answer(&name) {
+        save(config, &state).await?;
+        i.create_response(
+            ctx,
+            respond_ephemeral(t!(locale, "pokemon.wrong_remaining", remaining = remaining)),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    challenge.solvers.push(user_id);
+
+    let rank = challenge.solvers.len();
+    let streak = state.streaks.entry(user_id).or_default();
+
+    if streak.last_day != day {
+        streak.count = if streak.last_day == day - 1 {
+            streak.count + 1
+        } else {
+            1
+        };
+        streak.last_day = day;
+    }
+
+    let count = streak.count;
+
+    save(config, &state).await?;
+    i.create_response(
+        ctx,
+        respond_ephemeral(t!(
+            locale,
+            "pokemon.daily.correct",
+            rank = rank,
+            streak = count
+        )),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use crate::Error;
+use crate::bot::component::ComponentId;
+use crate::i18n::{self, Locale, t};
+use crate::pokemon::api::{self, Pokemon};
+use crate::pokemon::common::{COLOR_DEX, PokemonComponent};
+use futures::future::try_join_all;
+use poise::serenity_prelude as serenity;
+use pokerust::Id;
+
+pub const TYPES: &[&str] = &[
+    "normal", "fire", "water", "electric", "grass", "ice", "fighting", "poison", "ground",
+    "flying", "psychic", "bug", "rock", "ghost", "dragon", "dark", "steel", "fairy",
+];
+
+pub fn type_name(locale: Locale, slug: &str) -> &str {
+    i18n::get(locale, &format!("pokemon.types.{slug}")).unwrap_or(slug)
+}
+
+fn stat_name(locale: Locale, slug: &str) -> &str {
+    i18n::get(locale, &format!("pokemon.stats.{slug}")).unwrap_or(slug)
+}
+
+fn clean_flavor_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// 図鑑の表示状態。ボタンのcustom_idに埋め込む
+#[derive(Clone, Copy)]
+pub struct DexState {
+    pub species_id: i16,
+    pub variety: usize,
+    pub shiny: bool,
+}
+
+impl DexState {
+    pub fn new(species_id: i16) -> Self {
+        Self {
+            species_id,
+            variety: 0,
+            shiny: false,
+        }
+    }
+
+    fn custom_id(self) -> Result<String, Error> {
+        PokemonComponent::Dex(self).encode()
+    }
+}
+
+async fn species_display_name(id: i16) -> Result<String, Error> {
+    let pokemon = Pokemon { id };
+
+    Ok(match pokemon.name().await? {
+        Some(name) => name,
+        None => pokemon.name_in("en").await?.unwrap_or(id.to_string()),
+    })
+}
+
+/// 図鑑の埋め込みとボタンを作る。フォルムが存在しなければ`None`
+pub async fn build(
+    state: DexState,
+    locale: Locale,
+) -> Result<Option<(serenity::CreateEmbed, serenity::CreateActionRow)>, Error> {
+    let pokemon = Pokemon {
+        id: state.species_id,
+    };
+    let species = pokemon.species().await?;
+    let Some(variety) = pokemon.variety(state.variety).await? else {
+        return Ok(None);
+    };
+
+    let name_ja = pokemon.name().await?.unwrap_or(species.name.clone());
+    let name_en = pokemon.name_in("en").await?.unwrap_or(species.name.clone());
+    let title = if variety.is_default {
+        format!("#{:04} {name_ja} ({name_en})", species.id)
+    } else {
+        format!("#{:04} {name_ja} ({})", species.id, variety.name)
+    };
+
+    let types = variety
+        .types
+        .iter()
+        .map(|t| type_name(locale, &t.type_.name))
+        .collect::<Vec<_>>()
+        .join(" / ");
+
+    let language = match locale {
+        Locale::Ja => "ja-hrkt",
+        Locale::En => "en",
+    };
+    let abilities = try_join_all(variety.abilities.iter().map(|a| async move {
+        let name = api::ability_name(a.ability.id(), language)
+            .await?
+            .unwrap_or(a.ability.name.clone());
+
+        Ok::<_, Error>(if a.is_hidden {
+            t!(locale, "pokemon.dex.hidden_ability", name = name)
+        } else {
+            name
+        })
+    }))
+    .await?
+    .join("\n");
+
+    let total: u32 = variety.stats.iter().map(|s| u32::from(s.base_stat)).sum();
+    let stats = variety
+        .stats
+        .iter()
+        .map(|s| format!("{}: {}", stat_name(locale, &s.stat.name), s.base_stat))
+        .chain(std::iter::once(t!(
+            locale,
+            "pokemon.dex.total",
+            total = total
+        )))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let evolution = try_join_all(pokemon.evolution_stages().await?.into_iter().map(
+        |stage| async move {
+            Ok::<_, Error>(
+                try_join_all(stage.into_iter().map(species_display_name))
+                    .await?
+                    .join(" / "),
+            )
+        },
+    ))
+    .await?
+    .join(" → ");
+
+    let flavor_text = [
+        pokemon.latest_flavor_text_in("ja-hrkt").await?,
+        pokemon.latest_flavor_text_in("en").await?,
+    ]
+    .into_iter()
+    .flatten()
+    .map(|f| clean_flavor_text(&f))
+    .collect::<Vec<_>>()
+    .join("\n\n");
+
+    let sprite = if state.shiny {
+        variety
+            .sprites
+            .front_shiny
+            .as_ref()
+            .or(variety.sprites.front_default.as_ref())
+    } else {
+        variety.sprites.front_default.as_ref()
+    };
+
+    let mut embed = serenity::CreateEmbed::new()
+        .color(COLOR_DEX)
+        .title(title)
+        .description(flavor_text)
+        .field(t!(locale, "pokemon.dex.types"), types, true)
+        .field(t!(locale, "pokemon.dex.abilities"), abilities, true)
+        .field(t!(locale, "pokemon.dex.stats"), stats, false)
+        .field(t!(locale, "pokemon.dex.evolution"), evolution, false)
+        .footer(serenity::CreateEmbedFooter::new(format!(
+            "{}{}",
+            t!(
+                locale,
+                "pokemon.dex.form",
+                current = state.variety + 1,
+                count = species.varieties.len()
+            ),
+            if state.shiny {
+                t!(locale, "pokemon.dex.shiny_suffix")
+            } else {
+                String::new()
+            }
+        )));
+
+    if let Some(sprite) = sprite {
+        embed = embed.thumbnail(sprite);
+    }
+
+    let has_prev = state.variety > 0;
+    let has_next = state.variety + 1 < species.varieties.len();
+    let row = serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(
+            DexState {
+                variety: state.variety.saturating_sub(1),
+                ..state
+            }
+            .custom_id()?,
+        )
+        .label(t!(locale, "pokemon.dex.prev_form"))
+        .style(serenity::ButtonStyle::Secondary)
+        .disabled(!has_prev),
+        serenity::CreateButton::new(
+            DexState {
+                variety: state.variety + 1,
+                ..state
+            }
+            .custom_id()?,
+        )
+        .label(t!(locale, "pokemon.dex.next_form"))
+        .style(serenity::ButtonStyle::Secondary)
+        .disabled(!has_next),
+        serenity::CreateButton::new(
+            DexState {
+                shiny: !state.shiny,
+                ..state
+            }
+            .custom_id()?,
+        )
+        .label(if state.shiny {
+            t!(locale, "pokemon.dex.normal")
+        } else {
+            t!(locale, "pokemon.dex.shiny")
+        })
+        .style(serenity::ButtonStyle::Primary),
+    ]);
+
+    Ok(Some((embed, row)))
+}
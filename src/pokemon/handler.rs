@@ -0,0 +1,169 @@
+use crate::bot::component::ComponentId;
+use crate::i18n::{Locale, t};
+use crate::pokemon::common::{ANSWER_INPUT_ID, PokemonComponent};
+use crate::pokemon::daily;
+use crate::pokemon::dex::{self, DexState};
+use crate::pokemon::session::{self, AnswerInput, Verdict};
+use crate::{Data, Error};
+use poise::serenity_prelude as serenity;
+
+async fn handle_dex(
+    ctx: &serenity::Context,
+    i: &serenity::ComponentInteraction,
+    state: DexState,
+) -> Result<(), Error> {
+    let locale = Locale::from_discord(&i.locale);
+    let Some((embed, row)) = dex::build(state, locale).await? else {
+        i.create_response(
+            ctx,
+            serenity::CreateInteractionResponse::Message(
+                serenity::CreateInteractionResponseMessage::new()
+                    .content(t!(locale, "pokemon.form_not_found"))
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    i.create_response(
+        ctx,
+        serenity::CreateInteractionResponse::UpdateMessage(
+            serenity::CreateInteractionResponseMessage::new()
+                .embed(embed)
+                .components(vec![row]),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// 判定結果に応じた返答。正解・ギブアップは結果発表があるので確認だけ返す
+fn verdict_response(
+    verdict: Option<Verdict>,
+    locale: Locale,
+) -> serenity::CreateInteractionResponse {
+    let content = match verdict {
+        Some(Verdict::Correct | Verdict::GaveUp) => {
+            return serenity::CreateInteractionResponse::Acknowledge;
+        }
+        Some(Verdict::Wrong {
+            remaining: Some(remaining),
+        }) => t!(locale, "pokemon.wrong_remaining", remaining = remaining),
+        Some(Verdict::Wrong { remaining: None }) => t!(locale, "pokemon.wrong"),
+        Some(Verdict::AlreadyAnswered) => t!(locale, "pokemon.quiz.already_answered"),
+        None => t!(locale, "pokemon.quiz.finished"),
+    };
+
+    serenity::CreateInteractionResponse::Message(
+        serenity::CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true),
+    )
+}
+
+async fn open_quiz_modal(
+    ctx: &serenity::Context,
+    i: &serenity::ComponentInteraction,
+    session_id: u64,
+) -> Result<(), Error> {
+    let locale = Locale::from_discord(&i.locale);
+    let input = serenity::CreateInputText::new(
+        serenity::InputTextStyle::Short,
+        t!(locale, "pokemon.answer_label"),
+        ANSWER_INPUT_ID,
+    )
+    .required(true);
+
+    i.create_response(
+        ctx,
+        serenity::CreateInteractionResponse::Modal(
+            serenity::CreateModal::new(
+                PokemonComponent::QuizModal(session_id).encode()?,
+                t!(locale, "pokemon.quiz.modal_title"),
+            )
+            .components(vec![serenity::CreateActionRow::InputText(input)]),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn handle_component(
+    ctx: &serenity::Context,
+    _data: &Data,
+    i: &serenity::ComponentInteraction,
+    id: PokemonComponent,
+) -> Result<(), Error> {
+    match id {
+        PokemonComponent::Dex(state) => handle_dex(ctx, i, state).await,
+        PokemonComponent::QuizAnswer(session_id) => open_quiz_modal(ctx, i, session_id).await,
+        PokemonComponent::QuizChoice(session_id, index) => {
+            let verdict = session::submit(
+                i.channel_id,
+                session_id,
+                i.user.id,
+                AnswerInput::Choice(index),
+            )
+            .await;
+
+            i.create_response(
+                ctx,
+                verdict_response(verdict, Locale::from_discord(&i.locale)),
+            )
+            .await?;
+
+            Ok(())
+        }
+        PokemonComponent::DailyAnswer(day) => daily::handle_component(ctx, i, day).await,
+        PokemonComponent::QuizModal(_) | PokemonComponent::DailyModal(_) => Ok(()),
+    }
+}
+
+pub async fn handle_modal(
+    ctx: &serenity::Context,
+    data: &Data,
+    i: &serenity::ModalInteraction,
+    id: PokemonComponent,
+) -> Result<(), Error> {
+    let Some(answer) = i
+        .data
+        .components
+        .iter()
+        .flat_map(|row| &row.components)
+        .find_map(|c| match c {
+            serenity::ActionRowComponent::InputText(t) if t.custom_id == ANSWER_INPUT_ID => {
+                t.value.clone()
+            }
+            _ => None,
+        })
+    else {
+        return Ok(());
+    };
+
+    let session_id = match id {
+        PokemonComponent::DailyModal(day) => {
+            return daily::handle_modal(ctx, data, i, day, &answer).await;
+        }
+        PokemonComponent::QuizModal(session_id) => session_id,
+        _ => return Ok(()),
+    };
+    let verdict = session::submit(
+        i.channel_id,
+        session_id,
+        i.user.id,
+        AnswerInput::Text(answer),
+    )
+    .await;
+
+    i.create_response(
+        ctx,
+        verdict_response(verdict, Locale::from_discord(&i.locale)),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use crate::Error;
+use crate::i18n::{Locale, t};
+use crate::image::{alpha_to_mask, background, encode_webp};
+use crate::pokemon::api::{self, Pokemon};
+use crate::pokemon::dex::{TYPES, type_name};
+use crate::pokemon::quiz::{Question, QuizMode};
+use image::{DynamicImage, ImageReader};
+use poise::serenity_prelude as serenity;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::io::Cursor;
+
+const CHOICE_COUNT: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum QuizKind {
+    #[name = "シルエット"]
+    #[name_localized("en-US", "Silhouette")]
+    #[name_localized("en-GB", "Silhouette")]
+    Silhouette,
+    #[name = "鳴き声"]
+    #[name_localized("en-US", "Cry")]
+    #[name_localized("en-GB", "Cry")]
+    Cry,
+    #[name = "図鑑説明"]
+    #[name_localized("en-US", "Pokédex entry")]
+    #[name_localized("en-GB", "Pokédex entry")]
+    FlavorText,
+    #[name = "タイプ相性"]
+    #[name_localized("en-US", "Type matchup")]
+    #[name_localized("en-GB", "Type matchup")]
+    TypeMatchup,
+}
+
+impl QuizKind {
+    pub async fn question(self, mode: QuizMode, locale: Locale) -> Result<Question, Error> {
+        match self {
+            QuizKind::Silhouette => silhouette(mode, locale).await,
+            QuizKind::Cry => cry(mode, locale).await,
+            QuizKind::FlavorText => flavor_text(mode, locale).await,
+            QuizKind::TypeMatchup => type_matchup(locale).await,
+        }
+    }
+}
+
+pub async fn image(pokemon: &Pokemon) -> Result<DynamicImage, Error> {
+    let bytes = pokemon
+        .image_bytes()
+        .await?
+        .ok_or("ポケモンの画像が取得できませんでした")?;
+
+    Ok(ImageReader::new(Cursor::new(bytes.as_ref()))
+        .with_guessed_format()?
+        .decode()?)
+}
+
+pub async fn name(pokemon: &Pokemon) -> Result<String, Error> {
+    Ok(pokemon
+        .name()
+        .await?
+        .ok_or("ポケモンの名前が取得できませんでした")?)
+}
+
+/// 4択の選択肢（正解を含む）を作る
+async fn name_choices(pokemon: &Pokemon, name: &str) -> Result<Vec<String>, Error> {
+    let mut choices = vec![name.to_owned()];
+
+    while choices.len() < CHOICE_COUNT {
+        let other = Pokemon::random().await?;
+
+        if other.id == pokemon.id {
+            continue;
+        }
+        if let Some(other_name) = other.name().await?
+            && !choices.contains(&other_name)
+        {
+            choices.push(other_name);
+        }
+    }
+
+    choices.shuffle(&mut thread_rng());
+
+    Ok(choices)
+}
+
+/// 正解のポケモンを発表する文章
+pub async fn reveal(pokemon: &Pokemon, name: &str, locale: Locale) -> Result<String, Error> {
+    let flavor_text = pokemon
+        .flavor_text()
+        .await?
+        .map(|f| {
+            format!(
+                "\n{}",
+                t!(
+                    locale,
+                    "pokemon.question.description",
+                    text = f.replace('\n', "　")
+                )
+            )
+        })
+        .unwrap_or("".to_owned());
+
+    Ok(format!(
+        "{}\n\n{}{flavor_text}",
+        t!(locale, "pokemon.question.reveal", answer = name),
+        t!(locale, "pokemon.question.dex_number", id = pokemon.id)
+    ))
+}
+
+pub fn reveal_attachment(image: &DynamicImage) -> Result<serenity::CreateAttachment, Error> {
+    // TODO: ファイル名
+    Ok(serenity::CreateAttachment::bytes(
+        encode_webp(&background(image))?,
+        "pokemon.webp",
+    ))
+}
+
+/// ポケモンの名前を答える問題
+async fn name_question(
+    mode: QuizMode,
+    locale: Locale,
+    pokemon: &Pokemon,
+    image: &DynamicImage,
+    prompt: String,
+    attachment: Option<serenity::CreateAttachment>,
+) -> Result<Question, Error> {
+    let name = name(pokemon).await?;
+    let choices = match mode {
+        QuizMode::Free => Vec::new(),
+        QuizMode::Choice => name_choices(pokemon, &name).await?,
+    };
+
+    Ok(Question {
+        prompt,
+        attachment,
+        choices,
+        reveal: reveal(pokemon, &name, locale).await?,
+        reveal_attachment: Some(reveal_attachment(image)?),
+        answer: name,
+    })
+}
+
+async fn silhouette(mode: QuizMode, locale: Locale) -> Result<Question, Error> {
+    let pokemon = Pokemon::random().await?;
+    let image = image(&pokemon).await?;
+    let silhouette =
+        serenity::CreateAttachment::bytes(encode_webp(&alpha_to_mask(&image))?, "pokemon.webp");
+
+    name_question(
+        mode,
+        locale,
+        &pokemon,
+        &image,
+        t!(locale, "pokemon.question.silhouette"),
+        Some(silhouette),
+    )
+    .await
+}
+
+async fn cry(mode: QuizMode, locale: Locale) -> Result<Question, Error> {
+    let pokemon = Pokemon::random().await?;
+    let cry = pokemon
+        .cry_bytes()
+        .await?
+        .ok_or("ポケモンの鳴き声が取得できませんでした")?;
+    let attachment = serenity::CreateAttachment::bytes(cry.as_slice(), "cry.ogg");
+
+    name_question(
+        mode,
+        locale,
+        &pokemon,
+        &image(&pokemon).await?,
+        t!(locale, "pokemon.question.cry"),
+        Some(attachment),
+    )
+    .await
+}
+
+async fn flavor_text(mode: QuizMode, locale: Locale) -> Result<Question, Error> {
+    let pokemon = Pokemon::random().await?;
+    let name = name(&pokemon).await?;
+    let flavor_text = pokemon
+        .flavor_text()
+        .await?
+        .ok_or("ポケモンの説明が取得できませんでした")?
+        .replace('\n', "　")
+        .replace(&name, "〇〇〇");
+
+    name_question(
+        mode,
+        locale,
+        &pokemon,
+        &image(&pokemon).await?,
+        format!(
+            "{}\n> {flavor_text}",
+            t!(locale, "pokemon.question.flavor_text")
+        ),
+        None,
+    )
+    .await
+}
+
+fn multiplier_label(multiplier: f32) -> &'static str {
+    match (multiplier * 4.0) as u32 {
+        16 => "×4",
+        8 => "×2",
+        4 => "×1",
+        2 => "×½",
+        1 => "×¼",
+        _ => "×0",
+    }
+}
+
+async fn type_matchup(locale: Locale) -> Result<Question, Error> {
+    let pokemon = Pokemon::random().await?;
+    let name = name(&pokemon).await?;
+    let defense = pokemon.types().await?;
+    let attack = TYPES
+        .choose(&mut thread_rng())
+        .ok_or("タイプが見つかりませんでした")?;
+    let multiplier = api::damage_multiplier(attack, &defense).await?;
+
+    let mut breakdown = Vec::new();
+
+    for d in &defense {
+        let m = api::damage_multiplier(attack, std::slice::from_ref(d)).await?;
+
+        breakdown.push(format!(
+            "{} → {}：{}",
+            type_name(locale, attack),
+            type_name(locale, d),
+            multiplier_label(m)
+        ));
+    }
+
+    let choices: &[f32] = if defense.len() > 1 {
+        &[4.0, 2.0, 1.0, 0.5, 0.25, 0.0]
+    } else {
+        &[2.0, 1.0, 0.5, 0.0]
+    };
+    let defense_names = defense
+        .iter()
+        .map(|d| type_name(locale, d))
+        .collect::<Vec<_>>()
+        .join("/");
+    let attachment = reveal_attachment(&image(&pokemon).await?)?;
+
+    Ok(Question {
+        prompt: t!(
+            locale,
+            "pokemon.question.type_matchup",
+            attack = type_name(locale, attack),
+            name = name,
+            defense = defense_names
+        ),
+        attachment: Some(attachment),
+        answer: multiplier_label(multiplier).to_owned(),
+        choices: choices
+            .iter()
+            .map(|m| multiplier_label(*m).to_owned())
+            .collect(),
+        reveal: format!(
+            "{}\n\n{}",
+            t!(
+                locale,
+                "pokemon.question.reveal",
+                answer = multiplier_label(multiplier)
+            ),
+            breakdown.join("\n")
+        ),
+        reveal_attachment: None,
+    })
+}
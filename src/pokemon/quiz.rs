@@ -0,0 +1,264 @@
+use crate::bot::component::ComponentId;
+use crate::i18n::{Locale, t};
+use crate::pokemon::common::PokemonComponent;
+use crate::pokemon::session::{self, Answer, AnswerInput, SessionGuard, Verdict};
+use crate::{Context, Error};
+use futures::StreamExt;
+use poise::serenity_prelude::{self as serenity, Mentionable};
+use std::collections::HashSet;
+use tokio::sync::{mpsc, oneshot};
+use wana_kana::ConvertJapanese;
+
+#[derive(Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum QuizMode {
+    #[name = "自由回答"]
+    #[name_localized("en-US", "Free answer")]
+    #[name_localized("en-GB", "Free answer")]
+    Free,
+    #[name = "4択"]
+    #[name_localized("en-US", "Multiple choice")]
+    #[name_localized("en-GB", "Multiple choice")]
+    Choice,
+}
+
+/// 出題内容
+pub struct Question {
+    pub prompt: String,
+    pub attachment: Option<serenity::CreateAttachment>,
+    pub answer: String,
+    /// 選択肢。空なら自由回答
+    pub choices: Vec<String>,
+    pub reveal: String,
+    pub reveal_attachment: Option<serenity::CreateAttachment>,
+}
+
+impl Question {
+    fn mode(&self) -> QuizMode {
+        if self.choices.is_empty() {
+            QuizMode::Free
+        } else {
+            QuizMode::Choice
+        }
+    }
+}
+
+pub fn normalize_answer(answer: &str) -> String {
+    answer.trim().to_katakana()
+}
+
+/// `wrong`回間違えたあとに残っている回答回数。`max_retry`は案内に出す回答できる回数
+fn remaining_tries(max_retry: usize, wrong: usize) -> usize {
+    max_retry.saturating_sub(wrong)
+}
+
+/// 回答への返事。返信ならメッセージで、ボタンやモーダルなら`Verdict`で返す
+enum Respond {
+    Reply(Box<serenity::Message>),
+    Interaction(oneshot::Sender<Verdict>),
+}
+
+impl Respond {
+    async fn send(self, ctx: Context<'_>, verdict: Verdict, locale: Locale) -> Result<(), Error> {
+        match self {
+            Respond::Reply(m) => {
+                if let Verdict::Wrong { .. } = verdict {
+                    m.reply(ctx, t!(locale, "pokemon.wrong")).await?;
+                }
+            }
+            Respond::Interaction(tx) => {
+                let _ = tx.send(verdict);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 出題して、正解・ギブアップ・回答回数切れ・時間切れ・中断のいずれかまで回答を受け付ける
+pub async fn run(
+    ctx: Context<'_>,
+    session: SessionGuard,
+    mut answers: mpsc::UnboundedReceiver<Answer>,
+    question: Question,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let locale = Locale::of(ctx);
+    let mode = question.mode();
+    let time_limit = data.config.pokemon.time_limit;
+    let max_retry = data.config.pokemon.max_retry;
+    let normalized_answer = normalize_answer(&question.answer);
+
+    let (guide, rows) = match mode {
+        QuizMode::Free => (
+            t!(
+                locale,
+                "pokemon.quiz.guide_free",
+                minutes = time_limit.as_secs() / 60,
+                tries = max_retry
+            ),
+            vec![serenity::CreateActionRow::Buttons(vec![
+                serenity::CreateButton::new(PokemonComponent::QuizAnswer(session.id).encode()?)
+                    .label(t!(locale, "pokemon.answer_button"))
+                    .style(serenity::ButtonStyle::Primary),
+            ])],
+        ),
+        QuizMode::Choice => (
+            t!(
+                locale,
+                "pokemon.quiz.guide_choice",
+                minutes = time_limit.as_secs() / 60
+            ),
+            question
+                .choices
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    Ok(serenity::CreateButton::new(
+                        PokemonComponent::QuizChoice(session.id, i).encode()?,
+                    )
+                    .label(c)
+                    .style(serenity::ButtonStyle::Secondary))
+                })
+                .collect::<Result<Vec<_>, Error>>()?
+                .chunks(5)
+                .map(|c| serenity::CreateActionRow::Buttons(c.to_vec()))
+                .collect(),
+        ),
+    };
+
+    let mut reply = poise::CreateReply::default()
+        .content(format!("{}\n{guide}", question.prompt))
+        .components(rows);
+
+    if let Some(attachment) = question.attachment {
+        reply = reply.attachment(attachment);
+    }
+
+    let reply = ctx.send(reply).await?;
+    let reply_message_id = reply.message().await?.id;
+    let quiz_reference = || serenity::MessageReference::from((ctx.channel_id(), reply_message_id));
+
+    let mut collector = ctx
+        .channel_id()
+        .await_reply(ctx)
+        .filter(move |m| {
+            mode == QuizMode::Free
+                && m.message_reference
+                    .as_ref()
+                    .and_then(|r| r.message_id.as_ref())
+                    == Some(&reply_message_id)
+        })
+        .timeout(time_limit)
+        .stream();
+    let deadline = tokio::time::sleep(time_limit);
+    tokio::pin!(deadline);
+    let mut retry = 0;
+    let mut answered_users = HashSet::new();
+
+    let (result, reference) = loop {
+        let (user_id, answer, reference, respond) = tokio::select! {
+            Some(m) = collector.next() => {
+                let answer = normalize_answer(&m.content);
+
+                (m.author.id, answer, serenity::MessageReference::from(&m), Respond::Reply(Box::new(m)))
+            }
+            Some(a) = answers.recv() => {
+                let answer = match a.input {
+                    AnswerInput::Text(text) => normalize_answer(&text),
+                    AnswerInput::Choice(i) => question
+                        .choices
+                        .get(i)
+                        .map(|c| normalize_answer(c))
+                        .unwrap_or_default(),
+                };
+
+                (a.user_id, answer, quiz_reference(), Respond::Interaction(a.verdict))
+            }
+            () = &mut deadline => {
+                break (t!(locale, "pokemon.quiz.time_up"), quiz_reference());
+            }
+            () = session.stopped() => {
+                let result = if session::is_shutting_down() {
+                    t!(locale, "pokemon.quiz.shutdown")
+                } else {
+                    t!(locale, "pokemon.quiz.interrupted")
+                };
+
+                break (result, quiz_reference());
+            }
+        };
+
+        if mode == QuizMode::Choice && !answered_users.insert(user_id) {
+            respond.send(ctx, Verdict::AlreadyAnswered, locale).await?;
+
+            continue;
+        }
+
+        if answer == normalized_answer {
+            respond.send(ctx, Verdict::Correct, locale).await?;
+
+            break (
+                t!(locale, "pokemon.quiz.correct", user = user_id.mention()),
+                reference,
+            );
+        }
+
+        if mode == QuizMode::Free && answer == "ギブアップ" && user_id == ctx.author().id {
+            respond.send(ctx, Verdict::GaveUp, locale).await?;
+
+            break (t!(locale, "pokemon.quiz.gave_up"), reference);
+        }
+
+        retry += 1;
+
+        let remaining = (mode == QuizMode::Free).then(|| remaining_tries(max_retry, retry));
+
+        respond
+            .send(ctx, Verdict::Wrong { remaining }, locale)
+            .await?;
+
+        if remaining == Some(0) {
+            break (t!(locale, "pokemon.out_of_tries"), reference);
+        }
+    };
+
+    reply
+        .edit(ctx, poise::CreateReply::default().components(Vec::new()))
+        .await?;
+
+    let mut message = serenity::CreateMessage::new()
+        .reference_message(reference)
+        .content(format!("{result}\n{}", question.reveal))
+        .allowed_mentions(
+            serenity::CreateAllowedMentions::new()
+                .replied_user(false)
+                .everyone(false)
+                .all_users(false)
+                .all_roles(false),
+        );
+
+    if let Some(attachment) = question.reveal_attachment {
+        message = message.add_file(attachment);
+    }
+
+    ctx.channel_id().send_message(ctx, message).await?;
+
+    // 終了処理が発表まで待てるように、ここまで登録しておく
+    drop(session);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remaining_tries_ends_at_max_retry() {
+        let remaining: Vec<_> = (1..=3).map(|wrong| remaining_tries(3, wrong)).collect();
+
+        // 3回目の間違いで0になり、そこで締め切る
+        assert_eq!(remaining, [2, 1, 0]);
+        assert_eq!(remaining_tries(3, 4), 0);
+    }
+}
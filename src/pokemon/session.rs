@@ -0,0 +1,159 @@
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use poise::serenity_prelude as serenity;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use tokio::sync::{Notify, mpsc, oneshot};
+
+/// ボタンやモーダルから送られた回答
+pub enum AnswerInput {
+    Text(String),
+    Choice(usize),
+}
+
+pub enum Verdict {
+    Correct,
+    GaveUp,
+    /// 自由回答なら残りの回答可能回数
+    Wrong {
+        remaining: Option<usize>,
+    },
+    AlreadyAnswered,
+}
+
+pub struct Answer {
+    pub user_id: serenity::UserId,
+    pub input: AnswerInput,
+    pub verdict: oneshot::Sender<Verdict>,
+}
+
+struct Session {
+    id: u64,
+    owner: serenity::UserId,
+    stop: Arc<Notify>,
+    answers: mpsc::UnboundedSender<Answer>,
+}
+
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// ボットの終了処理で中断されたか
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// チャンネルごとに進行中のクイズ
+static SESSIONS: LazyLock<DashMap<serenity::ChannelId, Session>> = LazyLock::new(DashMap::new);
+
+/// 進行中のクイズ。dropされると登録が解除される
+pub struct SessionGuard {
+    pub id: u64,
+    channel_id: serenity::ChannelId,
+    stop: Arc<Notify>,
+}
+
+impl SessionGuard {
+    /// `/dareda stop`で中断されるまで待つ
+    pub async fn stopped(&self) {
+        self.stop.notified().await;
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        SESSIONS.remove_if(&self.channel_id, |_, s| s.id == self.id);
+    }
+}
+
+/// クイズを開始する。すでに進行中なら`None`
+pub fn start(
+    channel_id: serenity::ChannelId,
+    owner: serenity::UserId,
+) -> Option<(SessionGuard, mpsc::UnboundedReceiver<Answer>)> {
+    let Entry::Vacant(entry) = SESSIONS.entry(channel_id) else {
+        return None;
+    };
+    let id = NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed);
+    let stop = Arc::new(Notify::new());
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    entry.insert(Session {
+        id,
+        owner,
+        stop: Arc::clone(&stop),
+        answers: tx,
+    });
+
+    Some((
+        SessionGuard {
+            id,
+            channel_id,
+            stop,
+        },
+        rx,
+    ))
+}
+
+pub fn owner(channel_id: serenity::ChannelId) -> Option<serenity::UserId> {
+    SESSIONS.get(&channel_id).map(|s| s.owner)
+}
+
+/// 進行中のクイズを中断する。クイズがなければ`false`
+pub fn stop(channel_id: serenity::ChannelId) -> bool {
+    let Some(session) = SESSIONS.get(&channel_id) else {
+        return false;
+    };
+
+    session.stop.notify_one();
+
+    true
+}
+
+/// 回答を送る。クイズが終了していれば`None`
+pub async fn submit(
+    channel_id: serenity::ChannelId,
+    session_id: u64,
+    user_id: serenity::UserId,
+    input: AnswerInput,
+) -> Option<Verdict> {
+    let (tx, rx) = oneshot::channel();
+
+    {
+        let session = SESSIONS.get(&channel_id)?;
+
+        if session.id != session_id {
+            return None;
+        }
+
+        session
+            .answers
+            .send(Answer {
+                user_id,
+                input,
+                verdict: tx,
+            })
+            .ok()?;
+    }
+
+    rx.await.ok()
+}
+
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::Relaxed)
+}
+
+/// 終了処理のため、すべてのクイズを中断して結果の発表まで待つ。`timeout`までに終わらなかった数を返す
+pub async fn stop_all(timeout: Duration) -> usize {
+    SHUTTING_DOWN.store(true, Ordering::Relaxed);
+
+    for session in SESSIONS.iter() {
+        session.stop.notify_one();
+    }
+
+    let _ = tokio::time::timeout(timeout, async {
+        while !SESSIONS.is_empty() {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+
+    SESSIONS.len()
+}
@@ -1,3 +1,9 @@
 mod api;
+mod checker;
 pub mod command;
+pub mod common;
+mod export;
 pub mod handler;
+pub mod pool;
+mod source;
+mod store;
@@ -0,0 +1,752 @@
+use crate::proxy::api::{Anonymity, Auth, Protocol, ProxyAddr, ProxyCheckResult};
+use crate::{Error, config, http, metrics};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use futures::StreamExt;
+use regex::Regex;
+use reqwest::Url;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const GEOIP_BATCH_ENDPOINT: &str = "http://ip-api.com/batch?fields=query,countryCode";
+const GEOIP_BATCH_SIZE: usize = 100;
+
+const MAX_RESPONSE_BYTES: u64 = 64 * 1024;
+
+/// プロキシを経由していることを示すヘッダー
+const PROXY_HEADERS: &[&str] = &[
+    "via",
+    "forwarded",
+    "x-forwarded-for",
+    "x-real-ip",
+    "proxy-connection",
+    "x-proxy-id",
+    "client-ip",
+];
+
+static IPV4: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(?:\d{1,3}\.){3}\d{1,3}\b").unwrap());
+
+/// ヘッダーを返してくれる判定先
+pub struct Judge {
+    host: String,
+    port: u16,
+    path: String,
+    /// 判定先から見た自分のIPアドレス
+    real_ips: HashSet<String>,
+}
+
+impl Judge {
+    /// 判定先に直接アクセスして自分のIPアドレスを調べる
+    pub async fn new(judge_url: &str) -> Result<Self, Error> {
+        let url = Url::parse(judge_url).map_err(|e| format!("invalid judge_url: {e}"))?;
+
+        if url.scheme() != "http" {
+            return Err(format!("judge_url must be http: {judge_url}").into());
+        }
+
+        let host = url.host_str().ok_or("judge_url has no host")?.to_owned();
+        let port = url.port_or_known_default().unwrap_or(80);
+        let path = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_owned(),
+        };
+        let body = http::CLIENT
+            .get(judge_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let real_ips = IPV4
+            .find_iter(&body)
+            .map(|m| m.as_str().to_owned())
+            .collect();
+
+        Ok(Self {
+            host,
+            port,
+            path,
+            real_ips,
+        })
+    }
+
+    fn request(&self) -> String {
+        format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: Mozilla/5.0\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+            self.path, self.host
+        )
+    }
+
+    fn anonymity(&self, body: &str) -> Anonymity {
+        if IPV4
+            .find_iter(body)
+            .any(|m| self.real_ips.contains(m.as_str()))
+        {
+            return Anonymity::Transparent;
+        }
+
+        let lower = body.to_ascii_lowercase();
+
+        if PROXY_HEADERS.iter().any(|h| {
+            lower.contains(&format!("{h}:"))
+                || lower.contains(&format!("http_{}", h.replace('-', "_")))
+        }) {
+            Anonymity::Anonymous
+        } else {
+            Anonymity::Elite
+        }
+    }
+}
+
+async fn read_response<S>(stream: &mut S) -> Result<String, Error>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buf = Vec::new();
+
+    stream
+        .take(MAX_RESPONSE_BYTES)
+        .read_to_end(&mut buf)
+        .await?;
+
+    let response = String::from_utf8_lossy(&buf).into_owned();
+
+    if !(response.starts_with("HTTP/1.1 200") || response.starts_with("HTTP/1.0 200")) {
+        return Err("judge did not return 200".into());
+    }
+
+    Ok(response)
+}
+
+/// CONNECTでトンネルを張る
+async fn connect_http<S>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+    auth: Option<&Auth>,
+) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let authorization = auth.map_or("".to_owned(), |a| {
+        format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            BASE64.encode(format!("{}:{}", a.username, a.password))
+        )
+    });
+
+    stream
+        .write_all(
+            format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n{authorization}\r\n")
+                .as_bytes(),
+        )
+        .await?;
+
+    // ヘッダーの終わりまで1バイトずつ読む（トンネルの中身を読みすぎないため）
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() > 8 * 1024 || stream.read(&mut byte).await? == 0 {
+            return Err("invalid CONNECT response".into());
+        }
+
+        head.push(byte[0]);
+    }
+
+    let status = String::from_utf8_lossy(&head);
+
+    if !(status.starts_with("HTTP/1.1 200") || status.starts_with("HTTP/1.0 200")) {
+        return Err("CONNECT rejected".into());
+    }
+
+    Ok(())
+}
+
+async fn connect_socks4<S>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+    auth: Option<&Auth>,
+) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let ip = tokio::net::lookup_host((host, port))
+        .await?
+        .find_map(|a| match a.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .ok_or("judge has no IPv4 address")?;
+    let mut request = vec![0x04, 0x01];
+
+    request.extend_from_slice(&port.to_be_bytes());
+    request.extend_from_slice(&ip.octets());
+
+    // SOCKS4はパスワードがないのでユーザー名だけ送る
+    if let Some(auth) = auth {
+        request.extend_from_slice(auth.username.as_bytes());
+    }
+
+    request.push(0x00);
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 8];
+
+    stream.read_exact(&mut reply).await?;
+
+    if reply[1] != 0x5A {
+        return Err("SOCKS4 request rejected".into());
+    }
+
+    Ok(())
+}
+
+async fn connect_socks5<S>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+    auth: Option<&Auth>,
+) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    match auth {
+        Some(_) => stream.write_all(&[0x05, 0x02, 0x00, 0x02]).await?,
+        None => stream.write_all(&[0x05, 0x01, 0x00]).await?,
+    }
+
+    let mut method = [0u8; 2];
+
+    stream.read_exact(&mut method).await?;
+
+    match (method, auth) {
+        ([0x05, 0x00], _) => {}
+        // RFC 1929
+        ([0x05, 0x02], Some(auth)) => {
+            let mut request = vec![0x01, u8::try_from(auth.username.len())?];
+
+            request.extend_from_slice(auth.username.as_bytes());
+            request.push(u8::try_from(auth.password.len())?);
+            request.extend_from_slice(auth.password.as_bytes());
+            stream.write_all(&request).await?;
+
+            let mut status = [0u8; 2];
+
+            stream.read_exact(&mut status).await?;
+
+            if status[1] != 0x00 {
+                return Err("SOCKS5 authentication failed".into());
+            }
+        }
+        _ => return Err("SOCKS5 auth rejected".into()),
+    }
+
+    let host_len = u8::try_from(host.len())?;
+    let mut request = vec![0x05, 0x01, 0x00, 0x03, host_len];
+
+    request.extend_from_slice(host.as_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+
+    stream.read_exact(&mut reply).await?;
+
+    if reply[1] != 0x00 {
+        return Err("SOCKS5 request rejected".into());
+    }
+
+    let addr_len = match reply[3] {
+        0x01 => 4,
+        0x04 => 16,
+        0x03 => {
+            let mut len = [0u8; 1];
+
+            stream.read_exact(&mut len).await?;
+
+            usize::from(len[0])
+        }
+        _ => return Err("invalid SOCKS5 reply".into()),
+    };
+    let mut rest = vec![0u8; addr_len + 2];
+
+    stream.read_exact(&mut rest).await?;
+
+    Ok(())
+}
+
+/// インターネット上のアドレスか。ボットのホストから内部のネットワークを調べられないように、それ以外には接続しない
+fn is_global(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // 0.0.0.0/8、100.64.0.0/10（CGNAT）、198.18.0.0/15（ベンチマーク）、240.0.0.0/4（予約）
+                || a == 0
+                || (a == 100 && (b & 0xC0) == 64)
+                || (a == 198 && (b & 0xFE) == 18)
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_global(IpAddr::V4(ip));
+            }
+
+            let first = ip.segments()[0];
+
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // fc00::/7（ユニークローカル）、fe80::/10（リンクローカル）、2001:db8::/32（ドキュメント）
+                || (first & 0xFE00) == 0xFC00
+                || (first & 0xFFC0) == 0xFE80
+                || (first == 0x2001 && ip.segments()[1] == 0x0DB8))
+        }
+    }
+}
+
+/// 指定したプロトコルでプロキシを経由して判定先にアクセスする
+pub async fn check_protocol(
+    judge: &Judge,
+    addr: SocketAddr,
+    protocol: Protocol,
+    auth: Option<&Auth>,
+) -> Result<(Duration, Anonymity), Error> {
+    let started = Instant::now();
+    let mut stream = TcpStream::connect(addr).await?;
+    let (host, port) = (judge.host.as_str(), judge.port);
+
+    match protocol {
+        Protocol::Http => connect_http(&mut stream, host, port, auth).await?,
+        Protocol::Socks4 => connect_socks4(&mut stream, host, port, auth).await?,
+        Protocol::Socks5 => connect_socks5(&mut stream, host, port, auth).await?,
+    }
+
+    stream.write_all(judge.request().as_bytes()).await?;
+
+    let response = read_response(&mut stream).await?;
+
+    Ok((started.elapsed(), judge.anonymity(&response)))
+}
+
+/// スキームの指定がなければすべてのプロトコルを試して一番速かったものを返す
+pub async fn check_proxy(judge: &Judge, proxy: &ProxyAddr, timeout: Duration) -> ProxyCheckResult {
+    let mut result = ProxyCheckResult {
+        proxy: proxy.clone(),
+        working: false,
+        protocol: None,
+        latency: None,
+        anonymity: None,
+        country: None,
+    };
+    let addr = match tokio::time::timeout(
+        timeout,
+        tokio::net::lookup_host((proxy.host.as_str(), proxy.port)),
+    )
+    .await
+    {
+        Ok(Ok(mut addrs)) => addrs.find(|a| is_global(a.ip())),
+        _ => None,
+    };
+    let Some(addr) = addr else {
+        return result;
+    };
+    let protocols = proxy.scheme.map_or(Protocol::ALL.to_vec(), |p| vec![p]);
+    let auth = proxy.auth.as_ref();
+
+    let attempts = futures::future::join_all(protocols.into_iter().map(|protocol| async move {
+        let attempt =
+            tokio::time::timeout(timeout, check_protocol(judge, addr, protocol, auth)).await;
+
+        match attempt {
+            Ok(Ok((latency, anonymity))) => Some((protocol, latency, anonymity)),
+            _ => None,
+        }
+    }))
+    .await;
+
+    if let Some((protocol, latency, anonymity)) = attempts
+        .into_iter()
+        .flatten()
+        .min_by_key(|(_, latency, _)| *latency)
+    {
+        result.working = true;
+        result.protocol = Some(protocol);
+        result.latency = Some(latency);
+        result.anonymity = Some(anonymity);
+    }
+
+    result
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeoIp {
+    query: String,
+    country_code: Option<String>,
+}
+
+/// IPアドレスの国コードを調べる。失敗した場合は空
+async fn lookup_countries(ips: Vec<String>) -> HashMap<String, String> {
+    let mut countries = HashMap::new();
+
+    for chunk in ips.chunks(GEOIP_BATCH_SIZE) {
+        let response = async {
+            http::CLIENT
+                .post(GEOIP_BATCH_ENDPOINT)
+                .json(chunk)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Vec<GeoIp>>()
+                .await
+        }
+        .await;
+
+        match response {
+            Ok(list) => countries.extend(
+                list.into_iter()
+                    .filter_map(|g| Some((g.query, g.country_code.filter(|c| !c.is_empty())?))),
+            ),
+            Err(err) => tracing::warn!("geoip lookup error: {err}"),
+        }
+    }
+
+    countries
+}
+
+/// 同時実行数を制限してまとめてチェックする。結果は入力と同じ順で、国は調べない
+pub async fn check_all(
+    judge: &Judge,
+    config: &config::Proxy,
+    proxies: &[ProxyAddr],
+) -> Vec<ProxyCheckResult> {
+    futures::stream::iter(proxies.iter().cloned())
+        .map(|p| async move {
+            let started = Instant::now();
+            let result = check_proxy(judge, &p, config.check_timeout).await;
+
+            metrics::observe(
+                "bot_proxy_check_duration_seconds",
+                &[("working", if result.working { "true" } else { "false" })],
+                started.elapsed(),
+            );
+
+            result
+        })
+        .buffered(config.check_concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// 動作したプロキシの国を調べて埋める
+pub async fn fill_countries(results: &mut [ProxyCheckResult]) {
+    let working_ips = results
+        .iter()
+        .filter(|r| r.working)
+        .map(|r| r.proxy.host.clone())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let countries = lookup_countries(working_ips).await;
+
+    for result in results {
+        result.country = countries.get(&result.proxy.host).cloned();
+    }
+}
+
+/// 判定先を用意してチェックし、国も調べる
+pub async fn check_proxies(
+    config: &config::Proxy,
+    proxies: &[ProxyAddr],
+) -> Result<Vec<ProxyCheckResult>, Error> {
+    let judge = Judge::new(&config.judge_url).await?;
+    let mut results = check_all(&judge, config, proxies).await;
+
+    fill_countries(&mut results).await;
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    const JUDGE_BODY: &str = "REMOTE_ADDR = 198.51.100.1\nHTTP_USER_AGENT = Mozilla/5.0\n";
+
+    fn judge() -> Judge {
+        Judge {
+            host: "127.0.0.1".to_owned(),
+            port: 80,
+            path: "/".to_owned(),
+            real_ips: HashSet::from(["203.0.113.7".to_owned()]),
+        }
+    }
+
+    fn auth(username: &str, password: &str) -> Auth {
+        Auth {
+            username: username.to_owned(),
+            password: password.to_owned(),
+        }
+    }
+
+    async fn read_head(stream: &mut TcpStream) -> String {
+        let mut head = Vec::new();
+        let mut byte = [0u8; 1];
+
+        while !head.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).await.unwrap();
+            head.push(byte[0]);
+        }
+
+        String::from_utf8(head).unwrap()
+    }
+
+    async fn read_socks5_auth(stream: &mut TcpStream) -> (String, String) {
+        let mut header = [0u8; 2];
+
+        stream.read_exact(&mut header).await.unwrap();
+
+        let mut username = vec![0u8; usize::from(header[1])];
+
+        stream.read_exact(&mut username).await.unwrap();
+
+        let mut len = [0u8; 1];
+
+        stream.read_exact(&mut len).await.unwrap();
+
+        let mut password = vec![0u8; usize::from(len[0])];
+
+        stream.read_exact(&mut password).await.unwrap();
+
+        (
+            String::from_utf8(username).unwrap(),
+            String::from_utf8(password).unwrap(),
+        )
+    }
+
+    /// 1回だけ接続を受け付ける代わりのプロキシ。トンネルの先では判定先として`JUDGE_BODY`を返す
+    async fn stand_in(protocol: Protocol, expected_auth: Option<Auth>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            match protocol {
+                Protocol::Http => {
+                    let head = read_head(&mut stream).await;
+
+                    assert!(head.starts_with("CONNECT 127.0.0.1:80 HTTP/1.1\r\n"));
+
+                    if let Some(auth) = &expected_auth {
+                        let credentials =
+                            BASE64.encode(format!("{}:{}", auth.username, auth.password));
+
+                        assert!(
+                            head.contains(&format!("Proxy-Authorization: Basic {credentials}\r\n"))
+                        );
+                    }
+
+                    stream
+                        .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                        .await
+                        .unwrap();
+                }
+                Protocol::Socks4 => {
+                    let mut request = [0u8; 8];
+
+                    stream.read_exact(&mut request).await.unwrap();
+                    assert_eq!(request, [0x04, 0x01, 0, 80, 127, 0, 0, 1]);
+
+                    let mut user_id = Vec::new();
+                    let mut byte = [0u8; 1];
+
+                    loop {
+                        stream.read_exact(&mut byte).await.unwrap();
+
+                        if byte[0] == 0 {
+                            break;
+                        }
+
+                        user_id.push(byte[0]);
+                    }
+
+                    let expected = expected_auth.as_ref().map_or("", |a| &a.username);
+
+                    assert_eq!(user_id, expected.as_bytes());
+                    stream
+                        .write_all(&[0x00, 0x5A, 0, 0, 0, 0, 0, 0])
+                        .await
+                        .unwrap();
+                }
+                Protocol::Socks5 => {
+                    let mut greeting = [0u8; 2];
+
+                    stream.read_exact(&mut greeting).await.unwrap();
+
+                    let mut methods = vec![0u8; usize::from(greeting[1])];
+
+                    stream.read_exact(&mut methods).await.unwrap();
+
+                    match &expected_auth {
+                        Some(auth) => {
+                            assert!(methods.contains(&0x02));
+                            stream.write_all(&[0x05, 0x02]).await.unwrap();
+
+                            let (username, password) = read_socks5_auth(&mut stream).await;
+
+                            assert_eq!(
+                                (username.as_str(), password.as_str()),
+                                (auth.username.as_str(), auth.password.as_str())
+                            );
+                            stream.write_all(&[0x01, 0x00]).await.unwrap();
+                        }
+                        None => stream.write_all(&[0x05, 0x00]).await.unwrap(),
+                    }
+
+                    let mut request = [0u8; 5];
+
+                    stream.read_exact(&mut request).await.unwrap();
+                    assert_eq!(request[..4], [0x05, 0x01, 0x00, 0x03]);
+
+                    let mut host = vec![0u8; usize::from(request[4]) + 2];
+
+                    stream.read_exact(&mut host).await.unwrap();
+                    assert_eq!(&host[..host.len() - 2], b"127.0.0.1");
+                    stream
+                        .write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+                        .await
+                        .unwrap();
+                }
+            }
+
+            let request = read_head(&mut stream).await;
+
+            assert!(request.starts_with("GET / HTTP/1.1\r\n"));
+            stream
+                .write_all(format!("HTTP/1.1 200 OK\r\n\r\n{JUDGE_BODY}").as_bytes())
+                .await
+                .unwrap();
+        });
+
+        addr
+    }
+
+    async fn check(protocol: Protocol, auth: Option<Auth>) -> Anonymity {
+        let addr = stand_in(protocol, auth.clone()).await;
+        let (_, anonymity) = check_protocol(&judge(), addr, protocol, auth.as_ref())
+            .await
+            .unwrap();
+
+        anonymity
+    }
+
+    #[tokio::test]
+    async fn http_connect() {
+        assert_eq!(check(Protocol::Http, None).await, Anonymity::Elite);
+        assert_eq!(
+            check(Protocol::Http, Some(auth("user", "pass"))).await,
+            Anonymity::Elite
+        );
+    }
+
+    #[tokio::test]
+    async fn socks4() {
+        assert_eq!(check(Protocol::Socks4, None).await, Anonymity::Elite);
+        assert_eq!(
+            check(Protocol::Socks4, Some(auth("user", ""))).await,
+            Anonymity::Elite
+        );
+    }
+
+    #[tokio::test]
+    async fn socks5() {
+        assert_eq!(check(Protocol::Socks5, None).await, Anonymity::Elite);
+        assert_eq!(
+            check(Protocol::Socks5, Some(auth("user", "pass"))).await,
+            Anonymity::Elite
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_connect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            read_head(&mut stream).await;
+            stream
+                .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        assert!(
+            check_protocol(&judge(), addr, Protocol::Http, None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn anonymity() {
+        let judge = judge();
+
+        assert_eq!(
+            judge.anonymity("REMOTE_ADDR = 198.51.100.1\nHTTP_X_FORWARDED_FOR = 203.0.113.7\n"),
+            Anonymity::Transparent
+        );
+        assert_eq!(
+            judge.anonymity("REMOTE_ADDR = 198.51.100.1\nHTTP_VIA = 1.1 squid\n"),
+            Anonymity::Anonymous
+        );
+        assert_eq!(
+            judge.anonymity("Host: azenv.net\r\nForwarded: for=198.51.100.1\r\n"),
+            Anonymity::Anonymous
+        );
+        assert_eq!(judge.anonymity(JUDGE_BODY), Anonymity::Elite);
+    }
+
+    #[test]
+    fn global_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_global(ip.parse().unwrap()), "{ip}");
+        }
+
+        for ip in ["1.1.1.1", "8.8.8.8", "2606:4700:4700::1111"] {
+            assert!(is_global(ip.parse().unwrap()), "{ip}");
+        }
+    }
+}
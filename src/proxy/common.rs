@@ -0,0 +1,45 @@
+use crate::bot::component::ComponentId;
+
+pub enum ProxyComponent {
+    /// 保存した結果のIDを持つダウンロードボタン
+    DownloadStart(String),
+    /// 形式はボタンならcustom_idに、セレクトメニューなら選択した値にある
+    Download {
+        set_id: String,
+        format: Option<String>,
+    },
+}
+
+impl ComponentId for ProxyComponent {
+    const NAMESPACE: &'static str = "proxy";
+
+    fn fields(&self) -> Vec<String> {
+        match self {
+            ProxyComponent::DownloadStart(set_id) => {
+                vec!["download_start".to_owned(), set_id.clone()]
+            }
+            ProxyComponent::Download { set_id, format } => {
+                let mut fields = vec!["download".to_owned(), set_id.clone()];
+
+                fields.extend(format.clone());
+
+                fields
+            }
+        }
+    }
+
+    fn parse(fields: &[&str]) -> Option<Self> {
+        match fields {
+            ["download_start", set_id] => Some(ProxyComponent::DownloadStart(set_id.to_string())),
+            ["download", set_id] => Some(ProxyComponent::Download {
+                set_id: set_id.to_string(),
+                format: None,
+            }),
+            ["download", set_id, format] => Some(ProxyComponent::Download {
+                set_id: set_id.to_string(),
+                format: Some(format.to_string()),
+            }),
+            _ => None,
+        }
+    }
+}
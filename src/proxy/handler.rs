@@ -1,81 +1,81 @@
+use crate::bot::component::ComponentId;
+use crate::i18n::{Locale, t};
+use crate::proxy::common::ProxyComponent;
+use crate::proxy::export::{FORMATS, export};
+use crate::proxy::store;
 use crate::{Data, Error};
+use chrono::{DateTime, Utc};
 use poise::serenity_prelude as serenity;
-use regex::Regex;
-use std::borrow::Cow;
-use std::sync::LazyLock;
 
-struct ProxyInfo {
-    address: String,
-    typ: String,
-}
-
-const BUTTON_DEFINES: &[(&str, &str)] = &[
-    ("all", "All"),
-    ("http", "HTTP(s)"),
-    ("socks4", "Socks4"),
-    ("socks5", "Socks5"),
-    ("scheme", "All (+Scheme)"),
-];
-
-static EMBED_PROXY_SEP: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s*\|\s*").unwrap());
-
-fn read_embed_proxies(m: &serenity::Message) -> Option<Vec<ProxyInfo>> {
-    let lines = m.embeds.first().and_then(|e| e.description.as_ref())?;
-    let proxies: Vec<_> = lines
-        .lines()
-        .filter_map(|l| {
-            let parts: Vec<&str> = EMBED_PROXY_SEP.split(l).collect();
-            let (Some(address), Some(typ)) = (if parts.len() > 2 {
-                (parts.first(), parts.get(2))
-            } else {
-                (parts.first(), parts.get(1))
-            }) else {
-                return None;
-            };
+/// ボタンを1行に並べられる数
+const MAX_BUTTONS: usize = 5;
 
-            Some(ProxyInfo {
-                address: address.to_string(),
-                typ: typ.to_string(),
-            })
-        })
-        .collect();
-
-    (!proxies.is_empty()).then_some(proxies)
+fn respond_ephemeral(content: impl Into<String>) -> serenity::CreateInteractionResponse {
+    serenity::CreateInteractionResponse::Message(
+        serenity::CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true),
+    )
 }
 
 async fn handle_download_start(
     ctx: &serenity::Context,
     i: &serenity::ComponentInteraction,
+    id: &str,
 ) -> Result<(), Error> {
-    if read_embed_proxies(&i.message).is_none() {
+    let locale = Locale::from_discord(&i.locale);
+
+    if store::get(id).is_none() {
         i.create_response(
             ctx,
-            serenity::CreateInteractionResponse::Message(
-                serenity::CreateInteractionResponseMessage::new()
-                    .content("このダウンロードに関連付けられたプロキシが取得できません")
-                    .ephemeral(true),
-            ),
+            respond_ephemeral(t!(locale, "proxy.result_set_missing")),
         )
         .await?;
 
         return Ok(());
     };
 
-    let buttons: Vec<serenity::CreateButton> = BUTTON_DEFINES
-        .iter()
-        .map(|(id, label)| {
-            serenity::CreateButton::new(format!("proxy:download:{}", id))
-                .label(label.to_string())
-                .style(serenity::ButtonStyle::Secondary)
-        })
-        .collect();
-    let row = serenity::CreateActionRow::Buttons(buttons);
+    let row = if FORMATS.len() > MAX_BUTTONS {
+        let options = FORMATS
+            .iter()
+            .map(|(typ, label)| serenity::CreateSelectMenuOption::new(*label, *typ))
+            .collect();
+
+        serenity::CreateActionRow::SelectMenu(
+            serenity::CreateSelectMenu::new(
+                ProxyComponent::Download {
+                    set_id: id.to_owned(),
+                    format: None,
+                }
+                .encode()?,
+                serenity::CreateSelectMenuKind::String { options },
+            )
+            .placeholder(t!(locale, "proxy.download_type")),
+        )
+    } else {
+        serenity::CreateActionRow::Buttons(
+            FORMATS
+                .iter()
+                .map(|(typ, label)| {
+                    let custom_id = ProxyComponent::Download {
+                        set_id: id.to_owned(),
+                        format: Some(typ.to_string()),
+                    }
+                    .encode()?;
+
+                    Ok(serenity::CreateButton::new(custom_id)
+                        .label(label.to_string())
+                        .style(serenity::ButtonStyle::Secondary))
+                })
+                .collect::<Result<_, Error>>()?,
+        )
+    };
 
     i.create_response(
         ctx,
         serenity::CreateInteractionResponse::Message(
             serenity::CreateInteractionResponseMessage::new()
-                .content("Choose Download Type")
+                .content(t!(locale, "proxy.choose_download_type"))
                 .ephemeral(true)
                 .components(vec![row]),
         ),
@@ -85,86 +85,70 @@ async fn handle_download_start(
     Ok(())
 }
 
+async fn handle_download(
+    ctx: &serenity::Context,
+    i: &serenity::ComponentInteraction,
+    id: &str,
+    typ: &str,
+) -> Result<(), Error> {
+    let locale = Locale::from_discord(&i.locale);
+    let Some(set) = store::get(id) else {
+        i.create_response(
+            ctx,
+            respond_ephemeral(t!(locale, "proxy.result_set_missing")),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    let (content, extension) = export(&set.results, typ)?;
+    let timestamp = DateTime::<Utc>::from(set.created_at).format("%Y%m%d-%H%M%S");
+    let attachment = serenity::CreateAttachment::bytes(
+        content.as_bytes(),
+        format!("proxies-{timestamp}-{typ}.{extension}"),
+    );
+
+    i.create_response(
+        ctx,
+        serenity::CreateInteractionResponse::Message(
+            serenity::CreateInteractionResponseMessage::new()
+                .content(t!(locale, "proxy.download_complete"))
+                .add_file(attachment)
+                .ephemeral(true),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
 pub async fn handle_component(
     ctx: &serenity::Context,
     _data: &Data,
     i: &serenity::ComponentInteraction,
+    id: ProxyComponent,
 ) -> Result<(), Error> {
-    let id = i.data.custom_id.as_str();
-
     match id {
-        "proxy:download_start" => handle_download_start(ctx, i).await,
-        id if id.starts_with("proxy:download:") => {
-            let Some(typ) = id.strip_prefix("proxy:download:") else {
+        ProxyComponent::DownloadStart(set_id) => handle_download_start(ctx, i, &set_id).await,
+        ProxyComponent::Download { set_id, format } => {
+            let format = format.or_else(|| match &i.data.kind {
+                serenity::ComponentInteractionDataKind::StringSelect { values } => {
+                    values.first().cloned()
+                }
+                _ => None,
+            });
+            let Some(format) = format else {
                 i.create_response(
                     ctx,
-                    serenity::CreateInteractionResponse::Message(
-                        serenity::CreateInteractionResponseMessage::new()
-                            .content("不明なプロキシの種類です")
-                            .ephemeral(true),
-                    ),
+                    respond_ephemeral(t!(Locale::from_discord(&i.locale), "proxy.unknown_format")),
                 )
                 .await?;
 
                 return Ok(());
             };
-            let Some(proxies) = futures::future::OptionFuture::from(
-                i.message
-                    .message_reference
-                    .as_ref()
-                    .and_then(|r| r.message_id.as_ref())
-                    .map(|id| i.channel_id.message(ctx, id)),
-            )
-            .await
-            .and_then(|r| r.ok())
-            .and_then(|m| read_embed_proxies(&m)) else {
-                i.create_response(
-                    ctx,
-                    serenity::CreateInteractionResponse::Message(
-                        serenity::CreateInteractionResponseMessage::new()
-                            .content("このダウンロードに関連付けられたプロキシが取得できません")
-                            .ephemeral(true),
-                    ),
-                )
-                .await?;
-
-                return Ok(());
-            };
-
-            let download_proxies: Vec<Cow<'_, str>> = match typ {
-                "http" | "socks4" | "socks5" => proxies
-                    .iter()
-                    .filter(|p| p.typ == typ)
-                    .map(|p| Cow::Borrowed(p.address.as_str()))
-                    .collect(),
-                "scheme" => proxies
-                    .iter()
-                    .map(|p| Cow::Owned(format!("{}://{}", p.typ, p.address)))
-                    .collect(),
-                _ => proxies
-                    .iter()
-                    .map(|p| Cow::Borrowed(p.address.as_str()))
-                    .collect(),
-            };
-            let attachment = serenity::CreateAttachment::bytes(
-                download_proxies.join("\n").as_bytes(),
-                // TODO: ファイル名はランダムかタイムスタンプを含む
-                "proxies.txt",
-            );
-
-            i.create_response(
-                ctx,
-                serenity::CreateInteractionResponse::Message(
-                    serenity::CreateInteractionResponseMessage::new()
-                        .content("Complete")
-                        .add_file(attachment)
-                        .ephemeral(true),
-                ),
-            )
-            .await?;
 
-            Ok(())
+            handle_download(ctx, i, &set_id, &format).await
         }
-        _ => Ok(()),
     }
 }
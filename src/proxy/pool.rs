@@ -0,0 +1,106 @@
+use crate::config;
+use crate::proxy::api::{ProxyAddr, ProxyCheckResult};
+use crate::proxy::checker::check_proxies;
+use crate::proxy::source::fetch_all;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::collections::HashMap;
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant};
+
+struct PoolEntry {
+    result: ProxyCheckResult,
+    last_checked: Instant,
+    /// 連続で失敗した回数
+    failures: u32,
+}
+
+static POOL: LazyLock<RwLock<HashMap<ProxyAddr, PoolEntry>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// 一定間隔でプールを更新し続ける
+pub async fn refresh(config: config::Proxy) {
+    let mut interval = tokio::time::interval(config.refresh_interval);
+
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let started = Instant::now();
+
+        match refresh_once(&config).await {
+            Ok(()) => tracing::info!(
+                "proxy pool refreshed: {} working in {:?}",
+                len(),
+                started.elapsed()
+            ),
+            Err(err) => tracing::warn!("proxy pool refresh error: {err}"),
+        }
+    }
+}
+
+/// プールにあるものをすべて再チェックし、取得元から新しい候補を追加する
+async fn refresh_once(config: &config::Proxy) -> Result<(), crate::Error> {
+    let mut candidates: Vec<_> = POOL
+        .read()
+        .expect("failed to lock")
+        .keys()
+        .cloned()
+        .collect();
+    let fetched = fetch_all(config).await?;
+    let mut fresh: Vec<_> = {
+        let pool = POOL.read().expect("failed to lock");
+
+        fetched
+            .into_iter()
+            .filter(|p| !pool.contains_key(p))
+            .collect()
+    };
+
+    fresh.shuffle(&mut thread_rng());
+    fresh.truncate(config.refresh_batch);
+    candidates.extend(fresh);
+
+    let results = check_proxies(config, &candidates).await?;
+    let now = Instant::now();
+    let mut pool = POOL.write().expect("failed to lock");
+
+    for result in results {
+        if result.working {
+            pool.insert(
+                result.proxy.clone(),
+                PoolEntry {
+                    result,
+                    last_checked: now,
+                    failures: 0,
+                },
+            );
+        } else if let Some(entry) = pool.get_mut(&result.proxy) {
+            entry.failures += 1;
+            entry.last_checked = now;
+        }
+    }
+
+    pool.retain(|_, e| e.failures < config.max_failures);
+
+    Ok(())
+}
+
+fn len() -> usize {
+    POOL.read()
+        .expect("failed to lock")
+        .values()
+        .filter(|e| e.failures == 0)
+        .count()
+}
+
+/// 直近のチェックで動いていたものを返す
+pub fn fresh(max_age: Duration) -> Vec<ProxyCheckResult> {
+    POOL.read()
+        .expect("failed to lock")
+        .values()
+        .filter(|e| e.failures == 0 && e.last_checked.elapsed() <= max_age)
+        .map(|e| e.result.clone())
+        .collect()
+}
@@ -0,0 +1,161 @@
+use crate::proxy::api::ProxyAddr;
+use crate::{Error, config, http};
+use futures::future::{BoxFuture, join_all};
+use std::collections::HashSet;
+
+const PROXYSCRAPE_GET_PROXY_ENDPOINT: &str =
+    "https://api.proxyscrape.com/?request=displayproxies&proxytype=all&timeout=1500";
+
+/// プロキシの取得元
+pub trait ProxySource: Send + Sync {
+    fn name(&self) -> String;
+
+    fn fetch(&self) -> BoxFuture<'_, Result<Vec<ProxyAddr>, Error>>;
+}
+
+/// `ip:port`が1行ずつ並んだテキストを読む。読めない行は無視する
+fn parse_lines(text: &str) -> Vec<ProxyAddr> {
+    text.lines().filter_map(|l| l.parse().ok()).collect()
+}
+
+/// アップロードされたリストを読む。`ip:port`の行と`ip,port,...`のCSVに対応する
+pub fn parse_list(text: &str) -> Vec<ProxyAddr> {
+    let mut seen = HashSet::new();
+
+    text.lines()
+        .map(|l| l.trim().trim_start_matches('\u{feff}'))
+        .filter_map(|l| {
+            let fields: Vec<&str> = l.split(',').map(|f| f.trim()).collect();
+
+            match fields.as_slice() {
+                [host, port, ..] if port.parse::<u16>().is_ok() => {
+                    if host.contains(':') && !host.starts_with('[') {
+                        format!("[{host}]:{port}").parse().ok()
+                    } else {
+                        format!("{host}:{port}").parse().ok()
+                    }
+                }
+                [first, ..] => first.parse().ok(),
+                _ => None,
+            }
+        })
+        .filter(|p: &ProxyAddr| seen.insert(p.clone()))
+        .collect()
+}
+
+async fn fetch_text(url: &str) -> Result<String, Error> {
+    Ok(http::CLIENT
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?)
+}
+
+pub struct Proxyscrape;
+
+impl ProxySource for Proxyscrape {
+    fn name(&self) -> String {
+        "ProxyScrape".to_owned()
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<Vec<ProxyAddr>, Error>> {
+        Box::pin(async {
+            Ok(parse_lines(
+                &fetch_text(PROXYSCRAPE_GET_PROXY_ENDPOINT).await?,
+            ))
+        })
+    }
+}
+
+pub struct TextUrl {
+    pub url: String,
+}
+
+impl ProxySource for TextUrl {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<Vec<ProxyAddr>, Error>> {
+        Box::pin(async { Ok(parse_lines(&fetch_text(&self.url).await?)) })
+    }
+}
+
+pub struct Github {
+    pub repo: String,
+    pub branch: String,
+    pub path: String,
+}
+
+impl ProxySource for Github {
+    fn name(&self) -> String {
+        format!("github:{}/{}", self.repo, self.path)
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<Vec<ProxyAddr>, Error>> {
+        Box::pin(async {
+            let url = format!(
+                "https://raw.githubusercontent.com/{}/{}/{}",
+                self.repo, self.branch, self.path
+            );
+
+            Ok(parse_lines(&fetch_text(&url).await?))
+        })
+    }
+}
+
+pub struct LocalFile {
+    pub path: String,
+}
+
+impl ProxySource for LocalFile {
+    fn name(&self) -> String {
+        format!("file:{}", self.path)
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<Vec<ProxyAddr>, Error>> {
+        Box::pin(async { Ok(parse_lines(&tokio::fs::read_to_string(&self.path).await?)) })
+    }
+}
+
+pub fn from_config(source: &config::ProxySource) -> Box<dyn ProxySource> {
+    match source.clone() {
+        config::ProxySource::Proxyscrape => Box::new(Proxyscrape),
+        config::ProxySource::Url { url } => Box::new(TextUrl { url }),
+        config::ProxySource::Github { repo, branch, path } => {
+            Box::new(Github { repo, branch, path })
+        }
+        config::ProxySource::File { path } => Box::new(LocalFile { path }),
+    }
+}
+
+/// すべての取得元から集めて重複を除く。一部の取得元が失敗しても残りの結果を返す
+pub async fn fetch_all(config: &config::Proxy) -> Result<Vec<ProxyAddr>, Error> {
+    let sources: Vec<_> = config.sources.iter().map(from_config).collect();
+    let results = join_all(sources.iter().map(|s| s.fetch())).await;
+    let mut seen = HashSet::new();
+    let mut proxies = Vec::new();
+    let mut last_error = None;
+
+    for (source, result) in sources.iter().zip(results) {
+        match result {
+            Ok(list) => {
+                tracing::debug!("fetched {} proxies from {}", list.len(), source.name());
+
+                proxies.extend(list.into_iter().filter(|p| seen.insert(p.clone())));
+            }
+            Err(err) => {
+                tracing::warn!("fetch proxies from {} error: {err}", source.name());
+
+                last_error = Some(err);
+            }
+        }
+    }
+
+    match last_error {
+        Some(err) if proxies.is_empty() => Err(err),
+        _ => Ok(proxies),
+    }
+}
@@ -0,0 +1,52 @@
+use crate::proxy::api::ProxyCheckResult;
+use lru::LruCache;
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, SystemTime};
+
+const ID_LEN: usize = 8;
+const CAPACITY: NonZeroUsize = NonZeroUsize::new(256).unwrap();
+const TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// `/proxy`で表示した結果
+pub struct ResultSet {
+    pub created_at: SystemTime,
+    pub results: Vec<ProxyCheckResult>,
+}
+
+static RESULT_SETS: LazyLock<Mutex<LruCache<String, Arc<ResultSet>>>> =
+    LazyLock::new(|| Mutex::new(LruCache::new(CAPACITY)));
+
+/// 結果を保存してボタンのcustom_idに埋め込むIDを返す
+pub fn insert(results: Vec<ProxyCheckResult>) -> String {
+    let id: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(ID_LEN)
+        .map(char::from)
+        .collect();
+
+    RESULT_SETS.lock().expect("failed to lock").put(
+        id.clone(),
+        Arc::new(ResultSet {
+            created_at: SystemTime::now(),
+            results,
+        }),
+    );
+
+    id
+}
+
+pub fn get(id: &str) -> Option<Arc<ResultSet>> {
+    let mut sets = RESULT_SETS.lock().expect("failed to lock");
+    let set = sets.get(id)?.clone();
+
+    if set.created_at.elapsed().unwrap_or_default() > TTL {
+        sets.pop(id);
+
+        return None;
+    }
+
+    Some(set)
+}
@@ -1,23 +1,19 @@
-use crate::verify::common::{COLOR_AQUA, GUIDE_IMAGE_URL, START_ID};
+use crate::bot::check::{StaffOnly, is_staff};
+use crate::bot::component::ComponentId;
+use crate::i18n::{Locale, t};
+use crate::verify::common::{COLOR_AQUA, CaptchaComponent, GUIDE_IMAGE_URL};
 use crate::{Context, Error};
 use poise::serenity_prelude as serenity;
 
-async fn is_staff(ctx: Context<'_>) -> Result<bool, Error> {
-    let Some(member) = ctx.author_member().await else {
-        return Ok(false);
-    };
-    Ok(member
-        .roles
-        .contains(&ctx.data().config.guild.staff_role_id))
-}
-
 /// 認証パネルを設置
-#[poise::command(slash_command, guild_only)]
+#[poise::command(slash_command, guild_only, custom_data = "StaffOnly")]
 pub async fn captcha(ctx: Context<'_>) -> Result<(), Error> {
+    let locale = Locale::of(ctx);
+
     if !is_staff(ctx).await? {
         ctx.send(
             poise::CreateReply::default()
-                .content("権限がありません。")
+                .content(t!(locale, "common.no_permission"))
                 .ephemeral(true),
         )
         .await?;
@@ -28,8 +24,8 @@ pub async fn captcha(ctx: Context<'_>) -> Result<(), Error> {
         .color(COLOR_AQUA)
         .image(GUIDE_IMAGE_URL);
 
-    let button = serenity::CreateButton::new(START_ID)
-        .label("認証する")
+    let button = serenity::CreateButton::new(CaptchaComponent::Start.encode()?)
+        .label(t!(locale, "verify.start_button"))
         .style(serenity::ButtonStyle::Success);
 
     ctx.send(
@@ -1,3 +1,5 @@
+use crate::bot::component::ComponentId;
+
 // image
 pub const GUIDE_IMAGE_URL: &str =
     "https://r2.e-z.host/3d3d3396-6de1-4b53-9dfa-80964810a301/l5er5xu6.png";
@@ -9,5 +11,27 @@ pub const COLOR_AQUA: u32 = 0x8FD3FF;
 pub const COLOR_WHITE: u32 = 0xF5FAFF;
 pub const COLOR_FAIL: u32 = 0x9DB7C7;
 
-pub const START_ID: &str = "captcha:start";
-pub const ANSWER_PREFIX: &str = "captcha:ans:";
+pub enum CaptchaComponent {
+    Start,
+    /// 選んだ答え
+    Answer(u32),
+}
+
+impl ComponentId for CaptchaComponent {
+    const NAMESPACE: &'static str = "captcha";
+
+    fn fields(&self) -> Vec<String> {
+        match self {
+            CaptchaComponent::Start => vec!["start".to_owned()],
+            CaptchaComponent::Answer(n) => vec!["ans".to_owned(), n.to_string()],
+        }
+    }
+
+    fn parse(fields: &[&str]) -> Option<Self> {
+        match fields {
+            ["start"] => Some(CaptchaComponent::Start),
+            ["ans", n] => n.parse().ok().map(CaptchaComponent::Answer),
+            _ => None,
+        }
+    }
+}
@@ -1,7 +1,10 @@
+use crate::bot::component::ComponentId;
+use crate::bot::ratelimit;
+use crate::i18n::{Locale, t};
 use crate::verify::common::{
-    ANSWER_PREFIX, COLOR_AQUA, COLOR_FAIL, COLOR_WHITE, FOOTER_ICON_URL, START_ID,
+    COLOR_AQUA, COLOR_FAIL, COLOR_WHITE, CaptchaComponent, FOOTER_ICON_URL,
 };
-use crate::{Data, Error};
+use crate::{Data, Error, metrics};
 use dashmap::DashMap;
 use once_cell::sync::Lazy;
 use poise::serenity_prelude as serenity;
@@ -15,23 +18,52 @@ pub async fn handle_component(
     ctx: &serenity::Context,
     data: &Data,
     interaction: &serenity::ComponentInteraction,
+    id: CaptchaComponent,
 ) -> Result<(), Error> {
-    let id = interaction.data.custom_id.as_str();
-
-    if id == START_ID {
-        return on_start(ctx, interaction).await;
-    }
-    if let Some(rest) = id.strip_prefix(ANSWER_PREFIX) {
-        return on_answer(ctx, data, interaction, rest).await;
+    match id {
+        CaptchaComponent::Start => on_start(ctx, data, interaction).await,
+        CaptchaComponent::Answer(answered) => on_answer(ctx, data, interaction, answered).await,
     }
-    Ok(())
 }
 
 async fn on_start(
     ctx: &serenity::Context,
+    data: &Data,
     interaction: &serenity::ComponentInteraction,
 ) -> Result<(), Error> {
     let user_id = interaction.user.id;
+    let locale = Locale::from_discord(&interaction.locale);
+    let is_staff = interaction
+        .member
+        .as_ref()
+        .is_some_and(|m| m.roles.contains(&data.config.guild.staff_role_id));
+
+    if let Some(cooldown) = data.config.cooldowns.get("captcha_start")
+        && !is_staff
+        && let Some(wait) = ratelimit::hit(
+            "captcha_start",
+            ratelimit::scope_key(
+                cooldown.scope,
+                user_id,
+                interaction.channel_id,
+                interaction.guild_id,
+            ),
+            interaction.id.get(),
+            cooldown,
+        )
+    {
+        interaction
+            .create_response(
+                ctx,
+                serenity::CreateInteractionResponse::Message(
+                    serenity::CreateInteractionResponseMessage::new()
+                        .content(ratelimit::message(wait, locale))
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
 
     if let Some(existing) = CHALLENGES.get(&user_id) {
         if Instant::now() <= existing.expires_at {
@@ -40,7 +72,7 @@ async fn on_start(
                     ctx,
                     serenity::CreateInteractionResponse::Message(
                         serenity::CreateInteractionResponseMessage::new()
-                            .content("すでに挑戦中です。")
+                            .content(t!(locale, "verify.already_running"))
                             .ephemeral(true),
                     ),
                 )
@@ -77,18 +109,24 @@ async fn on_start(
 
     let embed = serenity::CreateEmbed::new()
         .color(COLOR_WHITE)
-        .title("認証チャレンジ")
+        .title(t!(locale, "verify.challenge_title"))
         .description(format!("**{a} × {b} = ?**"))
-        .footer(serenity::CreateEmbedFooter::new("制限時間：20秒"));
+        .footer(serenity::CreateEmbedFooter::new(t!(
+            locale,
+            "verify.time_limit",
+            secs = TIME_LIMIT.as_secs()
+        )));
 
     let buttons = choices
         .drain(..)
         .map(|n| {
-            serenity::CreateButton::new(format!("{ANSWER_PREFIX}{n}"))
-                .label(n.to_string())
-                .style(serenity::ButtonStyle::Secondary)
+            Ok(
+                serenity::CreateButton::new(CaptchaComponent::Answer(n).encode()?)
+                    .label(n.to_string())
+                    .style(serenity::ButtonStyle::Secondary),
+            )
         })
-        .collect();
+        .collect::<Result<_, Error>>()?;
 
     interaction
         .create_response(
@@ -117,25 +155,23 @@ async fn on_answer(
     ctx: &serenity::Context,
     data: &Data,
     interaction: &serenity::ComponentInteraction,
-    answered_str: &str,
+    answered: u32,
 ) -> Result<(), Error> {
     let user_id = interaction.user.id;
-    let answered: u32 = match answered_str.parse() {
-        Ok(v) => v,
-        Err(_) => return Ok(()),
-    };
+    let locale = Locale::from_discord(&interaction.locale);
 
-    let Some(ch) = CHALLENGES.get(&user_id).map(|v| v.clone()) else {
-        return Ok(());
-    };
+    let challenge = CHALLENGES.get(&user_id).map(|v| v.clone());
 
-    if Instant::now() > ch.expires_at {
-        CHALLENGES.remove(&user_id);
+    // 再起動などで問題が残っていなければ、時間切れと同じくやり直してもらう
+    let Some(ch) = challenge.filter(|ch| Instant::now() <= ch.expires_at) else {
+        if CHALLENGES.remove(&user_id).is_some() {
+            metrics::inc("bot_captcha_results_total", &[("result", "timeout")]);
+        }
 
         let embed = serenity::CreateEmbed::new()
             .color(COLOR_FAIL)
-            .title("⌛ 時間切れ")
-            .description("もう一度やり直してください。")
+            .title(t!(locale, "verify.timeout_title"))
+            .description(t!(locale, "verify.retry"))
             .footer(serenity::CreateEmbedFooter::new("Ayanamist System").icon_url(FOOTER_ICON_URL));
 
         interaction
@@ -149,15 +185,16 @@ async fn on_answer(
             )
             .await?;
         return Ok(());
-    }
+    };
 
     if answered != ch.correct {
         CHALLENGES.remove(&user_id);
+        metrics::inc("bot_captcha_results_total", &[("result", "wrong")]);
 
         let embed = serenity::CreateEmbed::new()
             .color(COLOR_FAIL)
-            .title("❌ 不正解")
-            .description("もう一度やり直してください。")
+            .title(t!(locale, "verify.wrong_title"))
+            .description(t!(locale, "verify.retry"))
             .footer(serenity::CreateEmbedFooter::new("Ayanamist System").icon_url(FOOTER_ICON_URL));
 
         interaction
@@ -182,11 +219,12 @@ async fn on_answer(
         .add_role(ctx, data.config.verify.verify_role_id)
         .await?;
     CHALLENGES.remove(&user_id);
+    metrics::inc("bot_captcha_results_total", &[("result", "passed")]);
 
     let embed = serenity::CreateEmbed::new()
         .color(COLOR_AQUA)
-        .title("✅ 認証成功")
-        .description("ロールを付与しました。")
+        .title(t!(locale, "verify.success_title"))
+        .description(t!(locale, "verify.success_description"))
         .footer(serenity::CreateEmbedFooter::new("Ayanamist System").icon_url(FOOTER_ICON_URL));
 
     interaction